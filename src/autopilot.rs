@@ -0,0 +1,353 @@
+use crate::*;
+use rand::prelude::random;
+use std::collections::HashMap;
+
+pub struct ModPlugin;
+
+// プレイヤーと同じ入力(回転の中心と、押しているか)を作って頭を動かすbot
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Autopilot>()
+            .add_system_to_stage(stage::PRE_UPDATE, bot_select_system.system())
+            .add_system_to_stage(stage::FIRST, autopilot_system.system());
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub fn next(self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Easy,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "EASY",
+            Difficulty::Normal => "NORMAL",
+            Difficulty::Hard => "HARD",
+        }
+    }
+
+    // 考え直すまでの秒数
+    fn reaction(self) -> f64 {
+        match self {
+            Difficulty::Easy => 0.4,
+            Difficulty::Normal => 0.2,
+            Difficulty::Hard => 0.05,
+        }
+    }
+
+    // 狙いのぶれ
+    fn aim_error(self) -> f32 {
+        match self {
+            Difficulty::Easy => 40.0,
+            Difficulty::Normal => 15.0,
+            Difficulty::Hard => 0.0,
+        }
+    }
+
+    // 前方のどこまで危険を見るか
+    fn lookahead(self) -> f32 {
+        match self {
+            Difficulty::Easy => 60.0,
+            Difficulty::Normal => 100.0,
+            Difficulty::Hard => 150.0,
+        }
+    }
+
+    // 曲がるときの回転半径
+    fn turn_radius(self) -> f32 {
+        match self {
+            Difficulty::Easy => 120.0,
+            Difficulty::Normal => 90.0,
+            Difficulty::Hard => 70.0,
+        }
+    }
+}
+
+// botの強さと、botごとの直前の判断
+pub struct Autopilot {
+    pub difficulty: Difficulty,
+    brains: HashMap<usize, Brain>,
+}
+
+impl Default for Autopilot {
+    fn default() -> Self {
+        Self {
+            difficulty: Difficulty::Normal,
+            brains: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Brain {
+    next_think_at: f64,
+    control: input::Control,
+}
+
+// Bでbotを加えたり外したり、Nで強さを切り替える。参加者が変わったら最初からやり直す
+fn bot_select_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    editor: Res<editor::Editor>,
+    session: Res<mode::Session>,
+    mut autopilot: ResMut<Autopilot>,
+    mut centipede_container: ResMut<CentipedeContainer>,
+    mut game_over_events: ResMut<Events<GameOver>>,
+) {
+    if editor.active {
+        return;
+    }
+    if keyboard_input.just_pressed(BOT_DIFFICULTY_KEY) {
+        autopilot.difficulty = autopilot.difficulty.next();
+    }
+    if !keyboard_input.just_pressed(BOT_KEY) {
+        return;
+    }
+
+    let players = &mut centipede_container.players;
+    let is_bot = |source: &input::InputSource| matches!(source, input::InputSource::Bot(_));
+    if players.iter().any(is_bot) {
+        if players.len() <= session.mode.min_players() {
+            return;
+        }
+        players.retain(|source| !is_bot(source));
+    } else {
+        players.push(input::InputSource::Bot(0));
+    }
+    centipede_container.finish(0.0, GameOverCause::Aborted, &mut game_over_events);
+}
+
+fn autopilot_system(
+    time: Res<clock::Clock>,
+    board: Res<Board>,
+    obstacles: Res<obstacle::Obstacles>,
+    centipede_container: Res<CentipedeContainer>,
+    mut autopilot: ResMut<Autopilot>,
+    mut controls: ResMut<input::Controls>,
+    head_query: Query<&Position, With<head::Head>>,
+    gate_query: Query<&Children, With<gate::Gate>>,
+    poll_query: Query<&GlobalTransform, With<gate::Poll>>,
+    tail_query: Query<(&tail::LivingTail, &Position)>,
+) {
+    let difficulty = autopilot.difficulty;
+
+    // 門ごとの両脇の柱
+    let gates: Vec<(Position, Position)> = gate_query
+        .iter()
+        .flat_map(|children| {
+            let polls: Vec<Position> = children
+                .iter()
+                .flat_map(|entity| poll_query.get(*entity))
+                .map(|transform| transform.translation.into())
+                .collect();
+            match polls.as_slice() {
+                [poll1, poll2] if poll1.visible && poll2.visible => Some((*poll1, *poll2)),
+                _ => None,
+            }
+        })
+        .collect();
+
+    for centipede in centipede_container.iter_alive() {
+        let bot = match centipede.input {
+            input::InputSource::Bot(bot) => bot,
+            _ => continue,
+        };
+        let brain = autopilot.brains.entry(bot).or_default();
+
+        if time.seconds_since_startup() >= brain.next_think_at {
+            brain.next_think_at = time.seconds_since_startup() + difficulty.reaction();
+            if let Ok(head) = head_query.get(centipede.head_entity) {
+                // 頭のすぐ後ろの尾は曲がっても当たらないので見ない
+                let tails: Vec<Position> = tail_query
+                    .iter()
+                    .filter(|(tail, position)| {
+                        tail.player == centipede.player
+                            && tail.index >= AUTOPILOT_IGNORED_TAILS
+                            && position.visible
+                    })
+                    .map(|(_, position)| *position)
+                    .collect();
+                brain.control = think(
+                    difficulty, centipede, head, &board, &obstacles, &gates, &tails,
+                );
+            }
+        }
+        controls.set(centipede.input, brain.control);
+    }
+}
+
+// 危険を避け、壁から離れ、一番近くの通れそうな門へ向かう
+fn think(
+    difficulty: Difficulty,
+    centipede: &Alive,
+    head: &Position,
+    board: &Board,
+    obstacles: &obstacle::Obstacles,
+    gates: &[(Position, Position)],
+    tails: &[Position],
+) -> input::Control {
+    let heading = normalize(centipede.last_move).unwrap_or(Vec2 { x: 1.0, y: 0.0 });
+    let radius = difficulty.turn_radius();
+
+    let target = if let Some(danger) =
+        danger_ahead(difficulty, head, heading, obstacles, gates, tails)
+    {
+        // 危険の反対側へ曲がる
+        let to_danger = Vec2::from(danger) - Vec2::from(*head);
+        let side = if cross(heading, to_danger) > 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        offset(head, left(heading) * side, radius)
+    } else if leaving_board(difficulty, head, heading, board) {
+        Position::default(true)
+    } else {
+        nearest_gate_target(head, heading, radius, gates).unwrap_or_else(|| Position::default(true))
+    };
+
+    let error = difficulty.aim_error();
+    let target = Position {
+        x: target.x + (random::<f32>() - 0.5) * error,
+        y: target.y + (random::<f32>() - 0.5) * error,
+        visible: true,
+    };
+    steer(head, heading, &target, radius)
+}
+
+// 前方の柱、自分の尾、障害物のうち一番近いもの
+fn danger_ahead(
+    difficulty: Difficulty,
+    head: &Position,
+    heading: Vec2,
+    obstacles: &obstacle::Obstacles,
+    gates: &[(Position, Position)],
+    tails: &[Position],
+) -> Option<Position> {
+    let lookahead = difficulty.lookahead();
+    let end = offset(head, heading, lookahead);
+    let in_path = |position: &Position, margin: f32| {
+        let ahead = heading.dot(Vec2::from(*position) - Vec2::from(*head));
+        ahead > 0.0 && geometry::distance_to_segment(position, head, &end) < margin
+    };
+
+    let polls = gates
+        .iter()
+        .flat_map(|(poll1, poll2)| vec![*poll1, *poll2])
+        .filter(|poll| in_path(poll, POLL_SIZE + HEAD_SIZE + AUTOPILOT_MARGIN));
+    let own_tails = tails
+        .iter()
+        .copied()
+        .filter(|tail| in_path(tail, TAIL_SIZE + AUTOPILOT_MARGIN));
+    let blocks = (1..=AUTOPILOT_PROBES)
+        .map(|step| {
+            offset(
+                head,
+                heading,
+                lookahead * step as f32 / AUTOPILOT_PROBES as f32,
+            )
+        })
+        .filter(|probe| obstacles.contains(probe));
+
+    polls
+        .chain(own_tails)
+        .chain(blocks)
+        .min_by(|a, b| head.distance(a).total_cmp(&head.distance(b)))
+}
+
+// 壁に向かっていて、もうすぐ跳ね返るか
+fn leaving_board(difficulty: Difficulty, head: &Position, heading: Vec2, board: &Board) -> bool {
+    let ahead = offset(head, heading, difficulty.lookahead());
+    ahead.x.abs() > board.x_border() || ahead.y.abs() > board.y_border()
+}
+
+// 回らずに済む門ほど近いとみなし、手前側から門の正面に回り込んでくぐる
+fn nearest_gate_target(
+    head: &Position,
+    heading: Vec2,
+    radius: f32,
+    gates: &[(Position, Position)],
+) -> Option<Position> {
+    gates
+        .iter()
+        .filter_map(|(poll1, poll2)| {
+            let center = poll1.forward_to(poll2, poll1.distance(poll2) / 2.0);
+            let along = normalize(Vec2::from(*poll2) - Vec2::from(*poll1))?;
+            let normal = left(along);
+            let to_head = Vec2::from(*head) - Vec2::from(center);
+            let side = if normal.dot(to_head) >= 0.0 {
+                1.0
+            } else {
+                -1.0
+            };
+
+            // 門の正面の通り道に入っていれば、反対側へ抜ける
+            let in_corridor = along.dot(to_head).abs() < poll1.distance(poll2) / 2.0 - POLL_SIZE
+                && normal.dot(to_head).abs() < AUTOPILOT_APPROACH * 1.5;
+            let target = if in_corridor {
+                offset(&center, normal * -side, AUTOPILOT_APPROACH)
+            } else {
+                offset(&center, normal * side, AUTOPILOT_APPROACH)
+            };
+
+            let to_target = Vec2::from(target) - Vec2::from(*head);
+            let turn = cross(heading, to_target)
+                .atan2(heading.dot(to_target))
+                .abs();
+            Some((head.distance(&target) + turn * radius, target))
+        })
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, target)| target)
+}
+
+// 向きが合っていれば離してまっすぐ進み、合っていなければ曲がる側に中心を置いて押す
+fn steer(head: &Position, heading: Vec2, target: &Position, radius: f32) -> input::Control {
+    let to_target = Vec2::from(*target) - Vec2::from(*head);
+    let angle = cross(heading, to_target).atan2(heading.dot(to_target));
+    let side = if angle > 0.0 { 1.0 } else { -1.0 };
+    let center = offset(head, left(heading) * side, radius);
+
+    // 目標が回転の内側にあると回り続けてしまうので、一度まっすぐ離れる
+    let pressed = angle.abs() > AUTOPILOT_ALIGN_RADIAN && center.distance(target) >= radius;
+    input::Control {
+        position: if pressed { center } else { *head },
+        pressed,
+        ..Default::default()
+    }
+}
+
+fn normalize(vec: Vec2) -> Option<Vec2> {
+    let length = vec.length();
+    if length > 0.0 {
+        Some(vec / length)
+    } else {
+        None
+    }
+}
+
+fn left(vec: Vec2) -> Vec2 {
+    Vec2 {
+        x: -vec.y,
+        y: vec.x,
+    }
+}
+
+fn cross(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+fn offset(position: &Position, direction: Vec2, distance: f32) -> Position {
+    let mut position = *position;
+    position.move_to_with_distance(direction, distance);
+    position
+}
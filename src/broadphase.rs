@@ -0,0 +1,126 @@
+use crate::*;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+pub struct ModPlugin;
+
+// 当たり判定の前に、ぶつかるかもしれないものをパネルごとに振り分けておく
+// 物が動くので毎刻み作り直す
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Broadphase>()
+            .add_system_to_stage(stage::BROADPHASE, build_broadphase_system.system());
+    }
+}
+
+// パネルと同じ大きさの升目ごとに、そこに掛かっているものを持つ
+pub struct Grid<T> {
+    board: Board,
+    cells: HashMap<Panel, Vec<T>>,
+}
+
+impl<T> Default for Grid<T> {
+    fn default() -> Self {
+        Self {
+            board: Board::default(),
+            cells: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Copy + Eq + Hash> Grid<T> {
+    fn clear(&mut self, board: Board) {
+        self.board = board;
+        self.cells.clear();
+    }
+
+    // fromからtoまでを囲む四角に掛かる升目
+    fn cells(&self, from: &Position, to: &Position, reach: f32) -> Vec<Panel> {
+        let min = self.board.cell(&Position {
+            x: from.x.min(to.x) - reach,
+            y: from.y.min(to.y) - reach,
+            visible: true,
+        });
+        let max = self.board.cell(&Position {
+            x: from.x.max(to.x) + reach,
+            y: from.y.max(to.y) + reach,
+            visible: true,
+        });
+        (min.x..=max.x)
+            .flat_map(|x| (min.y..=max.y).map(move |y| Panel { x, y }))
+            .collect()
+    }
+
+    // fromからtoまで動いたもの(線分のもの)を入れる。点ならfromとtoを同じにする
+    pub fn insert(&mut self, from: &Position, to: &Position, item: T) {
+        for cell in self.cells(from, to, 0.0) {
+            self.cells.entry(cell).or_default().push(item);
+        }
+    }
+
+    // fromからtoまで動く間にreach以内に入るかもしれないもの。重なりは除く
+    pub fn query(&self, from: &Position, to: &Position, reach: f32) -> Vec<T> {
+        let mut seen = HashSet::new();
+        self.cells(from, to, reach)
+            .iter()
+            .flat_map(|cell| self.cells.get(cell))
+            .flatten()
+            .filter(|item| seen.insert(**item))
+            .copied()
+            .collect()
+    }
+}
+
+// 当たり判定の相手の種類ごとの升目
+#[derive(Default)]
+pub struct Broadphase {
+    pub heads: Grid<Entity>,
+    // 前の刻みの位置から今の位置まで
+    pub tails: Grid<Entity>,
+    // 両脇の柱を結ぶ線分
+    pub gates: Grid<Entity>,
+    pub enemies: Grid<Entity>,
+}
+
+fn build_broadphase_system(
+    board: Res<Board>,
+    mut broadphase: ResMut<Broadphase>,
+    head_query: Query<(Entity, &Position), With<head::Head>>,
+    tail_query: Query<(Entity, &GlobalTransform, &Position), With<tail::LivingTail>>,
+    gate_query: Query<(Entity, &Children), With<gate::Gate>>,
+    poll_query: Query<&GlobalTransform, With<gate::Poll>>,
+    enemy_query: Query<(Entity, &Position), With<enemy::Enemy>>,
+) {
+    let broadphase = &mut *broadphase;
+    broadphase.heads.clear(*board);
+    broadphase.tails.clear(*board);
+    broadphase.gates.clear(*board);
+    broadphase.enemies.clear(*board);
+
+    for (entity, position) in head_query.iter() {
+        broadphase.heads.insert(position, position, entity);
+    }
+
+    for (entity, global_transform, position) in tail_query.iter().filter(|(_, _, p)| p.visible) {
+        let from = Some(Position::from(global_transform.translation))
+            .filter(|from| from.visible)
+            .unwrap_or(*position);
+        broadphase.tails.insert(&from, position, entity);
+    }
+
+    for (entity, children) in gate_query.iter() {
+        let polls: Vec<Position> = children
+            .iter()
+            .flat_map(|poll_entity| poll_query.get(*poll_entity))
+            .map(|global_transform| Position::from(global_transform.translation))
+            .filter(|position| position.visible)
+            .collect();
+        if let (Some(first), Some(last)) = (polls.first(), polls.last()) {
+            broadphase.gates.insert(first, last, entity);
+        }
+    }
+
+    for (entity, position) in enemy_query.iter() {
+        broadphase.enemies.insert(position, position, entity);
+    }
+}
@@ -0,0 +1,47 @@
+use crate::*;
+
+pub struct ModPlugin;
+
+// ゲームの中の時計。どのsystemもbevyのTimeではなくこれを見る
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Clock>()
+            .add_system_to_stage(stage::FIRST, clock_system.system());
+    }
+}
+
+// 普段は実時間に合わせて進む。stepを決めておくと、実時間に関係なく毎回その秒数だけ進む
+#[derive(Default, Debug)]
+pub struct Clock {
+    pub step: Option<f64>,
+    delta_seconds: f64,
+    seconds_since_startup: f64,
+}
+
+impl Clock {
+    pub fn fixed(step: f64) -> Self {
+        Self {
+            step: Some(step),
+            ..Default::default()
+        }
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta_seconds as f32
+    }
+
+    pub fn seconds_since_startup(&self) -> f64 {
+        self.seconds_since_startup
+    }
+
+    fn advance(&mut self, delta_seconds: f64) {
+        self.delta_seconds = delta_seconds;
+        self.seconds_since_startup += delta_seconds;
+    }
+}
+
+// bevyのTimeの更新より後、他のどのsystemよりも先に進める
+fn clock_system(time: Res<Time>, mut clock: ResMut<Clock>) {
+    let delta_seconds = clock.step.unwrap_or_else(|| time.delta_seconds_f64());
+    clock.advance(delta_seconds);
+}
@@ -1,19 +1,43 @@
-use bevy::{prelude::Vec3, render::color::Color};
+use bevy::{
+    prelude::{KeyCode, Vec3},
+    render::color::Color,
+};
 
 pub const INITIAL_CENTIPEDE_LENGTH: usize = 10;
 
+// 当たり判定で同じ点とみなす距離
+pub const GEOMETRY_EPSILON: f32 = 0.001;
+
+// プレイヤーごとの頭、尾、回転の中心の色
+pub const PLAYER_COLORS: [Color; 3] = [Color::BLUE, Color::PURPLE, Color::TEAL];
+pub const PLAYER_START_SPACING: f32 = 120.0;
+
 pub const DEFAULT_SPEED: f32 = 100.0;
 
 pub const HEAD_SIZE: f32 = 14.0;
-pub const HEAD_COLOR: Color = Color::BLUE;
+// 頭のすぐ後ろのこの数の尾には、自分では当たらない
+pub const SELF_COLLISION_GRACE_TAILS: usize = 2;
 
 pub const MARKER_SIZE: f32 = 6.0;
-pub const MARKER_COLOR: Color = Color::BLUE;
+// 回る半径の下限と上限。外れていたら少しずつ近づける
+// 下限は頭の大きさより大きくして、頭のすぐ後ろの尾に重ならないようにする
+pub const ORBIT_MIN_RADIUS: f32 = 20.0;
+pub const ORBIT_MAX_RADIUS: f32 = 250.0;
+// 半径が外れている分を一秒でどれだけ詰めるか
+pub const ORBIT_RADIUS_EASE: f32 = 3.0;
+// 回りながら締めたり緩めたりするとき、一秒で半径が変わる量
+pub const SPIRAL_SPEED: f32 = 80.0;
+// マウスのホイール一目盛りで、締めたり緩めたりし続ける秒数
+pub const SPIRAL_WHEEL_SECONDS: f64 = 0.15;
+// 回転の中心に出す、回る円の目印
+pub const ORBIT_RING_DOTS: usize = 24;
+pub const ORBIT_RING_DOT_SIZE: f32 = 1.5;
 
 pub const TAIL_SIZE: f32 = 18.0;
-pub const TAIL_COLOR: Color = Color::BLUE;
 pub const PURGED_COLOR: Color = Color::RED;
 pub const TAIL_DISTANCE: f32 = 30.0;
+// 頭の跡は尾の数よりこれだけ多めに残す。尾が伸びたときにすぐ並べられるように
+pub const POSITION_HISTORY_SPARE_TAILS: usize = 10;
 
 pub const GATE_MIN_WIDTH: f32 = 100.0;
 pub const GATE_MAX_WIDTH: f32 = 180.0;
@@ -22,6 +46,10 @@ pub const POLL_SIZE: f32 = 12.0;
 pub const BAR_COLOR: Color = Color::LIME_GREEN;
 pub const BAR_DIAMETER: f32 = 10.0;
 pub const GATE_SPAWN_PER_SECONDS: f64 = 2.0;
+pub const SPINNING_BAR_COLOR: Color = Color::CYAN;
+pub const DRIFTING_BAR_COLOR: Color = Color::GOLD;
+pub const GATE_SPIN_SPEED: f32 = 0.8;
+pub const GATE_DRIFT_SPEED: f32 = 30.0;
 
 pub const LIGHT_COORDINATE: Vec3 = Vec3 {
     x: 0.0,
@@ -37,12 +65,50 @@ pub const FPS_COLOR: Color = Color::WHITE;
 
 pub const SCORE_PREFIX: &str = "SCORE:";
 pub const SPEED_PREFIX: &str = "SPEED:";
+// 回っている間、一秒で速くなる量
 pub const SPEED_UP: f32 = 3.0;
+// 回っても、これより速くはならない
+pub const SPEED_CAP: f32 = 400.0;
+// まっすぐ進んでいる間、一秒で遅くなる量
+pub const SPEED_DECAY: f32 = 1.5;
+// ブレーキで一秒に落とす速さと、それ以上は落とさない速さ
+pub const BRAKE_DECELERATION: f32 = 120.0;
+pub const BRAKE_MIN_SPEED: f32 = DEFAULT_SPEED / 2.0;
+// ブレーキでこれだけ落とすごとに、スコアを払う。足りなければ尾を一つ失う
+pub const BRAKE_SPEED_PER_COST: f32 = 20.0;
+pub const BRAKE_SCORE_COST: usize = 10;
+// ブーストで一度に上がる速さと、次に使えるまでの秒数
+pub const BOOST_SPEED: f32 = 60.0;
+pub const BOOST_COOLDOWN_SECONDS: f64 = 5.0;
+// ブーストで上限を超えた分が、一秒で戻る量
+pub const BOOST_DECAY: f32 = 20.0;
+// 速さ、ブレーキ、ブーストの目盛り
+pub const BRAKE_PREFIX: &str = "BRAKE:";
+pub const BOOST_PREFIX: &str = "BOOST:";
+// 点や失った尾の数を、その場所に浮かべて上へ流す
+pub const POPUP_SIZE: f32 = 20.0;
+pub const POPUP_COLOR: Color = Color::GOLD;
+pub const POPUP_LOSS_COLOR: Color = Color::RED;
+pub const POPUP_SECONDS: f32 = 1.2;
+pub const POPUP_RISE_SPEED: f32 = 40.0;
+pub const CRASH_TEXT: &str = "CRASH";
+pub const GAUGE_WIDTH: usize = 10;
+pub const GAUGE_FULL: char = '#';
+pub const GAUGE_EMPTY: char = '-';
+pub const GAUGE_READY: &str = "READY";
+pub const GAUGE_SIZE: f32 = 18.0;
+pub const GAUGE_COLOR: Color = Color::WHITE;
 pub const HIGH_SCORE_PREFIX: &str = "HIGH:";
 pub const TAIL_PREFIX: &str = "TAIL:";
 pub const SCORE_SIZE: f32 = 24.0;
 pub const SCORE_COLOR: Color = Color::WHITE;
 pub const GATE_NOT_SPAWN_DISTANCE_TO_HEAD: f32 = 100.0;
+// ゲーム開始のシードから作る乱数の使い道
+pub const GATE_RNG_STREAM: u64 = 0;
+pub const OBSTACLE_RNG_STREAM: u64 = 1;
+pub const ENEMY_RNG_STREAM: u64 = 2;
+// 門の置き場所を探す回数。見つからなければその回は出さない
+pub const GATE_POSITION_ATTEMPTS: usize = 32;
 
 pub const VISIBLE_OBJECT_Z: f32 = 10.0;
 pub const INVISIBLE_OBJECT_Z: f32 = -1000.0;
@@ -56,8 +122,153 @@ pub const INVISIBLE_POSITION: Vec3 = Vec3 {
 pub const PANEL_SIZE: f32 = 50.0;
 pub const PANEL_X_COUNT: isize = 25;
 pub const PANEL_Y_COUNT: isize = 13;
+// ステージの盤面に許す一番小さいパネルの数
+pub const MIN_BOARD_PANELS: isize = 5;
 pub const PANEL_COLOR: Color = Color::GRAY;
-pub const BOARD_Y_SIZE: f32 = PANEL_Y_COUNT as f32 * PANEL_SIZE;
-pub const BOARD_Y_BORDER: f32 = BOARD_Y_SIZE / 2.0;
-pub const BOARD_X_SIZE: f32 = PANEL_X_COUNT as f32 * PANEL_SIZE;
-pub const BOARD_X_BORDER: f32 = BOARD_X_SIZE / 2.0;
+
+pub const ENEMY_SIZE: f32 = 12.0;
+pub const SEEKER_COLOR: Color = Color::PINK;
+pub const PATROLLER_COLOR: Color = Color::YELLOW;
+pub const BOUNCER_COLOR: Color = Color::PURPLE;
+pub const ENEMY_FIRST_SPAWN_SECONDS: f64 = 10.0;
+pub const ENEMY_SPAWN_PER_SECONDS: f64 = 12.0;
+pub const ENEMY_MAX_COUNT: usize = 6;
+pub const ENEMY_SPEED: f32 = 60.0;
+pub const ENEMY_SPEED_UP: f32 = 0.5;
+pub const ENEMY_TURN_SPEED: f32 = 1.5;
+pub const ENEMY_NOT_SPAWN_DISTANCE_TO_HEAD: f32 = 300.0;
+// 敵の置き場所を探す回数。見つからなければその回は出さない
+pub const ENEMY_POSITION_ATTEMPTS: usize = 32;
+pub const PATROLLER_SIGHT: f32 = 200.0;
+
+pub const OBSTACLE_COUNT: usize = 12;
+pub const OBSTACLE_SIZE: f32 = PANEL_SIZE - 6.0;
+pub const WALL_COLOR: Color = Color::SILVER;
+pub const ASTEROID_COLOR: Color = Color::MAROON;
+pub const OBSTACLE_NOT_SPAWN_DISTANCE_TO_HEAD: f32 = 200.0;
+
+pub const ASSET_DIRECTORY: &str = "assets";
+pub const LEVEL_DIRECTORY: &str = "levels";
+pub const LEVEL_EXTENSION: &str = "ron";
+pub const LEVEL_KEYS: [KeyCode; 10] = [
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+pub const LEVEL_PREFIX: &str = "LEVEL:";
+pub const ENDLESS_LEVEL_NAME: &str = "ENDLESS";
+pub const GOAL_PREFIX: &str = "GATES:";
+pub const PAR_PREFIX: &str = "PAR:";
+pub const CLEAR_TEXT: &str = "CLEAR!";
+pub const LEVEL_SIZE: f32 = 20.0;
+pub const LEVEL_COLOR: Color = Color::WHITE;
+
+pub const EDITOR_KEY: KeyCode = KeyCode::E;
+pub const PLAY_TEST_KEY: KeyCode = KeyCode::P;
+pub const SAVE_KEY: KeyCode = KeyCode::S;
+pub const GATE_TOOL_KEY: KeyCode = KeyCode::G;
+pub const OBSTACLE_TOOL_KEY: KeyCode = KeyCode::O;
+pub const START_TOOL_KEY: KeyCode = KeyCode::H;
+pub const DELETE_KEYS: [KeyCode; 2] = [KeyCode::Delete, KeyCode::Back];
+pub const EDITOR_PICK_DISTANCE: f32 = 40.0;
+pub const EDITOR_ROTATE_STEP: f32 = 15.0;
+pub const EDITOR_WIDTH_STEP: f32 = 10.0;
+pub const EDITOR_MIN_GATE_WIDTH: f32 = 40.0;
+pub const EDITOR_MAX_GATE_WIDTH: f32 = 400.0;
+pub const EDITOR_DEFAULT_GATE_WIDTH: f32 = 140.0;
+pub const EDITOR_NEW_LEVEL_NAME: &str = "Custom";
+pub const EDITOR_MARKER_COLOR: Color = Color::CYAN;
+pub const EDITOR_PREFIX: &str = "EDITOR";
+pub const EDITOR_SIZE: f32 = 20.0;
+pub const EDITOR_COLOR: Color = Color::CYAN;
+
+pub const MODE_KEY: KeyCode = KeyCode::M;
+pub const RESTART_SECONDS: f64 = 2.0;
+pub const TIME_ATTACK_SECONDS: f64 = 90.0;
+pub const TALLY_SECONDS: f64 = 6.0;
+pub const LEADERBOARD_SIZE: usize = 10;
+pub const MODE_PREFIX: &str = "MODE:";
+pub const TIME_PREFIX: &str = "TIME:";
+pub const RANK_PREFIX: &str = "RANK:";
+pub const TIME_UP_TEXT: &str = "TIME UP!";
+pub const TALLY_SIZE: f32 = 32.0;
+pub const TALLY_COLOR: Color = Color::WHITE;
+
+pub const ZEN_MIN_TAIL: usize = 5;
+pub const ZEN_REGROW_SECONDS: f64 = 1.0;
+pub const ZEN_SPEED_STEP: f32 = 20.0;
+pub const ZEN_MIN_SPEED: f32 = 40.0;
+pub const ZEN_MAX_SPEED: f32 = 400.0;
+pub const SPEED_UP_KEY: KeyCode = KeyCode::Up;
+pub const SPEED_DOWN_KEY: KeyCode = KeyCode::Down;
+pub const PIN_SPEED_KEY: KeyCode = KeyCode::L;
+pub const PINNED_TEXT: &str = "PINNED";
+
+pub const SURVIVAL_DECAY_SECONDS: f64 = 4.0;
+pub const SURVIVAL_MIN_DECAY_SECONDS: f64 = 0.8;
+pub const SURVIVAL_DECAY_ACCELERATION: f64 = 0.02;
+pub const SURVIVAL_WARNING_SECONDS: f64 = 1.0;
+pub const DECAY_PREFIX: &str = "DECAY:";
+pub const DECAY_WARNING_COLOR: Color = Color::RED;
+
+pub const JOIN_KEY: KeyCode = KeyCode::J;
+pub const KEYBOARD_UP_KEY: KeyCode = KeyCode::W;
+pub const KEYBOARD_DOWN_KEY: KeyCode = KeyCode::S;
+pub const KEYBOARD_LEFT_KEY: KeyCode = KeyCode::A;
+pub const KEYBOARD_RIGHT_KEY: KeyCode = KeyCode::D;
+pub const KEYBOARD_HOLD_KEY: KeyCode = KeyCode::Space;
+pub const KEYBOARD_TIGHTEN_KEY: KeyCode = KeyCode::Z;
+pub const KEYBOARD_LOOSEN_KEY: KeyCode = KeyCode::X;
+pub const KEYBOARD_BRAKE_KEY: KeyCode = KeyCode::C;
+pub const KEYBOARD_BOOST_KEY: KeyCode = KeyCode::V;
+// マウスで遊ぶときのブレーキ。ブーストは中ボタン
+pub const MOUSE_BRAKE_KEY: KeyCode = KeyCode::LShift;
+pub const KEYBOARD_CURSOR_SPEED: f32 = 400.0;
+pub const PLAYER_PREFIX: &str = "P";
+
+pub const VERSUS_ROUNDS: usize = 3;
+// 対戦で相手の尾を一つ奪うごとの点
+pub const VERSUS_STEAL_POINTS: usize = 5;
+pub const ROUND_PREFIX: &str = "ROUND";
+pub const BEST_OF_PREFIX: &str = "BEST OF";
+pub const ROUND_WON_TEXT: &str = "TAKES THE ROUND";
+pub const MATCH_WON_TEXT: &str = "WINS THE MATCH!";
+pub const DRAW_TEXT: &str = "DRAW";
+pub const ROUND_SIZE: f32 = 28.0;
+pub const ROUND_COLOR: Color = Color::WHITE;
+
+pub const BOT_KEY: KeyCode = KeyCode::B;
+pub const BOT_DIFFICULTY_KEY: KeyCode = KeyCode::N;
+pub const BOT_PREFIX: &str = "BOT:";
+pub const AUTOPILOT_MARGIN: f32 = 10.0;
+pub const AUTOPILOT_APPROACH: f32 = 60.0;
+pub const AUTOPILOT_ALIGN_RADIAN: f32 = 0.15;
+pub const AUTOPILOT_PROBES: usize = 4;
+pub const AUTOPILOT_IGNORED_TAILS: usize = 3;
+
+pub const ATTRACT_BOT: usize = 0;
+pub const PRESS_START_TEXT: &str = "PRESS ANY KEY TO START";
+pub const TITLE_TEXT: &str = "SPACE CENTIPEDE";
+pub const TITLE_SIZE: f32 = 48.0;
+pub const TITLE_COLOR: Color = Color::WHITE;
+pub const PRESS_START_BLINK_SECONDS: f64 = 0.6;
+
+pub const SEED_LOCK_KEY: KeyCode = KeyCode::R;
+pub const SEED_PREFIX: &str = "SEED:";
+pub const LOCKED_TEXT: &str = "LOCKED";
+pub const GHOST_COLOR: Color = Color::rgba_linear(0.6, 0.6, 1.0, 0.3);
+pub const GHOST_SAMPLE_SECONDS: f64 = 0.05;
+// 覚えておく走りの数
+pub const GHOST_MAX_RUNS: usize = 32;
+pub const GHOST_DELTA_SECONDS: f64 = 2.0;
+pub const GHOST_PREFIX: &str = "GHOST";
+pub const GHOST_AHEAD_COLOR: Color = Color::RED;
+pub const GHOST_BEHIND_COLOR: Color = Color::LIME_GREEN;
+pub const GHOST_SIZE: f32 = 28.0;
@@ -0,0 +1,88 @@
+use crate::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+// ランダムな門の出し方。今回のゲームの経過秒数に対する曲線で決める
+// スコアで変えると同じシードでも遊び方で門が変わり、幽霊と比べられなくなるので使わない
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Director {
+    // これより少なければすぐに出す
+    pub min_gates: usize,
+    // これ以上は出さない
+    pub max_gates: usize,
+    // 出す間隔(秒)
+    pub interval: Curve,
+    // 門の幅の範囲
+    pub min_width: Curve,
+    pub max_width: Curve,
+    // 門の種類ごとの出やすさ
+    pub kinds: Vec<KindWeight>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct KindWeight {
+    pub kind: gate::GateKind,
+    pub by_time: Curve,
+}
+
+impl Default for Director {
+    fn default() -> Self {
+        Self {
+            min_gates: 2,
+            max_gates: 12,
+            interval: Curve(vec![
+                (0.0, GATE_SPAWN_PER_SECONDS as f32),
+                (180.0, 1.0),
+                (480.0, 0.7),
+            ]),
+            min_width: Curve(vec![(0.0, GATE_MIN_WIDTH), (120.0, 70.0), (360.0, 60.0)]),
+            max_width: Curve(vec![(0.0, GATE_MAX_WIDTH), (120.0, 130.0), (360.0, 110.0)]),
+            kinds: vec![
+                KindWeight {
+                    kind: gate::GateKind::Normal,
+                    by_time: Curve::constant(1.0),
+                },
+                KindWeight {
+                    kind: gate::GateKind::Spinning,
+                    by_time: Curve(vec![(0.0, 0.0), (30.0, 0.3), (120.0, 0.6)]),
+                },
+                KindWeight {
+                    kind: gate::GateKind::Drifting,
+                    by_time: Curve(vec![(60.0, 0.0), (180.0, 0.25), (300.0, 0.5)]),
+                },
+            ],
+        }
+    }
+}
+
+impl Director {
+    pub fn interval(&self, elapsed: f32) -> f32 {
+        self.interval.sample(elapsed)
+    }
+
+    pub fn width(&self, elapsed: f32, rng: &mut impl Rng) -> f32 {
+        let (min, max) = (
+            self.min_width.sample(elapsed),
+            self.max_width.sample(elapsed),
+        );
+        min + rng.gen::<f32>() * (max - min).max(0.0)
+    }
+
+    // 重みに従ってランダムに選ぶ。全部0なら普通の門
+    pub fn kind(&self, elapsed: f32, rng: &mut impl Rng) -> gate::GateKind {
+        let weights: Vec<_> = self
+            .kinds
+            .iter()
+            .map(|weight| (weight.kind, weight.by_time.sample(elapsed).max(0.0)))
+            .collect();
+
+        let mut rest = rng.gen::<f32>() * weights.iter().map(|(_, w)| w).sum::<f32>();
+        for (kind, weight) in weights {
+            if rest < weight {
+                return kind;
+            }
+            rest -= weight;
+        }
+        gate::GateKind::Normal
+    }
+}
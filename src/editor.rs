@@ -0,0 +1,347 @@
+use crate::*;
+use obstacle::ObstacleKind;
+
+pub struct ModPlugin;
+
+// ステージエディタ。選択中のステージを直接書き換える
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Editor>()
+            .add_startup_system(setup.system())
+            .add_system_to_stage(stage::PRE_UPDATE, toggle_editor_system.system())
+            .add_system_to_stage(stage::UPDATE, edit_system.system().chain(void.system()))
+            .add_system_to_stage(stage::POST_UPDATE, marker_system.system());
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Tool {
+    // 門の配置、選択、移動
+    Gate,
+    // パネルに障害物を塗る
+    Obstacle,
+    // 頭の出発地点と向き
+    Start,
+}
+
+pub struct Editor {
+    pub active: bool,
+    pub tool: Tool,
+    // 選択中の門の番号
+    pub selected: Option<usize>,
+    dragging: bool,
+    // 塗っている最中の障害物。Someの中のNoneは消しゴム
+    painting: Option<Option<ObstacleKind>>,
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self {
+            active: false,
+            tool: Tool::Gate,
+            selected: None,
+            dragging: false,
+            painting: None,
+        }
+    }
+}
+
+struct StartMarker {}
+
+struct DirectionMarker {}
+
+struct SelectionMarker {}
+
+fn setup(
+    commands: &mut Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let material = materials.add(EDITOR_MARKER_COLOR.into());
+    let mut marker = |commands: &mut Commands, radius: f32| {
+        commands
+            .spawn(PbrBundle {
+                mesh: meshes.add(Mesh::from(shape::Icosphere {
+                    radius,
+                    subdivisions: 3,
+                })),
+                material: material.clone(),
+                ..Default::default()
+            })
+            .with(Position::default(false));
+    };
+
+    marker(commands, HEAD_SIZE);
+    commands.with(StartMarker {});
+    marker(commands, MARKER_SIZE);
+    commands.with(DirectionMarker {});
+    marker(commands, MARKER_SIZE);
+    commands.with(SelectionMarker {});
+}
+
+// Eでエディタの出入り、Pで出てそのままテストプレイ
+fn toggle_editor_system(
+    time: Res<clock::Clock>,
+    keyboard_input: Res<Input<KeyCode>>,
+    board: Res<Board>,
+    mut editor: ResMut<Editor>,
+    mut levels: ResMut<level::Levels>,
+    mut centipede_container: ResMut<CentipedeContainer>,
+    mut game_over_events: ResMut<Events<GameOver>>,
+    mut level_edited_events: ResMut<Events<LevelEdited>>,
+) {
+    if editor.active {
+        if keyboard_input.just_pressed(EDITOR_KEY) || keyboard_input.just_pressed(PLAY_TEST_KEY) {
+            *editor = Editor::default();
+            centipede_container.restart();
+        }
+        return;
+    }
+
+    if !keyboard_input.just_pressed(EDITOR_KEY) {
+        return;
+    }
+
+    centipede_container.finish(
+        time.seconds_since_startup(),
+        GameOverCause::Aborted,
+        &mut game_over_events,
+    );
+
+    // エンドレスなら新しいステージを作る
+    if levels.current().is_none() {
+        let level = level::Level::new(EDITOR_NEW_LEVEL_NAME.to_string(), *board);
+        levels.list.push(level);
+        levels.selected = Some(levels.list.len() - 1);
+    }
+
+    editor.active = true;
+    level_edited_events.send(LevelEdited {});
+}
+
+fn edit_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    cursor_state: Res<input::CursorState>,
+    board: Res<Board>,
+    mut editor: ResMut<Editor>,
+    mut levels: ResMut<level::Levels>,
+    mut level_edited_events: ResMut<Events<LevelEdited>>,
+) -> Option<()> {
+    if !editor.active {
+        return None;
+    }
+    let level = levels.current_mut()?;
+
+    for (key, tool) in &[
+        (GATE_TOOL_KEY, Tool::Gate),
+        (OBSTACLE_TOOL_KEY, Tool::Obstacle),
+        (START_TOOL_KEY, Tool::Start),
+    ] {
+        if keyboard_input.just_pressed(*key) {
+            editor.tool = *tool;
+        }
+    }
+
+    if keyboard_input.just_pressed(SAVE_KEY) {
+        save_level(level);
+    }
+
+    if !cursor_state.left_pressed {
+        editor.dragging = false;
+        editor.painting = None;
+    }
+
+    let cursor = cursor_state.position;
+    let rotate = if keyboard_input.just_pressed(KeyCode::Left) {
+        EDITOR_ROTATE_STEP
+    } else if keyboard_input.just_pressed(KeyCode::Right) {
+        -EDITOR_ROTATE_STEP
+    } else {
+        0.0
+    };
+
+    let edited = match editor.tool {
+        Tool::Gate => edit_gate(&mut editor, level, &keyboard_input, &cursor_state, rotate),
+        Tool::Obstacle => {
+            let panel = board.panel(&cursor)?;
+            let current = level
+                .obstacles
+                .iter()
+                .find(|spec| spec.panel == panel)
+                .map(|spec| spec.kind);
+
+            // 押した場所の状態を 無し→壁→小惑星→無し と進め、ドラッグ中は同じものを塗る
+            if cursor_state.left_just_pressed {
+                editor.painting = Some(match current {
+                    None => Some(ObstacleKind::Wall),
+                    Some(ObstacleKind::Wall) => Some(ObstacleKind::Asteroid),
+                    Some(ObstacleKind::Asteroid) => None,
+                });
+            }
+            match editor.painting {
+                Some(paint) if paint != current => {
+                    level.obstacles.retain(|spec| spec.panel != panel);
+                    if let Some(kind) = paint {
+                        level.obstacles.push(level::ObstacleSpec { panel, kind });
+                    }
+                    true
+                }
+                _ => false,
+            }
+        }
+        Tool::Start => {
+            if cursor_state.left_pressed {
+                level.start.x = cursor.x;
+                level.start.y = cursor.y;
+            }
+            level.start.direction = (level.start.direction + rotate) % 360.0;
+            cursor_state.left_pressed || rotate != 0.0
+        }
+    };
+
+    if edited {
+        level_edited_events.send(LevelEdited {});
+    }
+    None
+}
+
+// クリックで門を選ぶか置く。左右キーで回転、上下キーで幅、Deleteで削除
+fn edit_gate(
+    editor: &mut Editor,
+    level: &mut level::Level,
+    keyboard_input: &Input<KeyCode>,
+    cursor_state: &input::CursorState,
+    rotate: f32,
+) -> bool {
+    let cursor = cursor_state.position;
+    let mut edited = false;
+
+    if cursor_state.left_just_pressed {
+        editor.selected = level.gates.iter().position(|gate| {
+            cursor.distance(&Position {
+                x: gate.x,
+                y: gate.y,
+                visible: true,
+            }) <= EDITOR_PICK_DISTANCE
+        });
+        if editor.selected.is_none() {
+            level.gates.push(level::GateSpec {
+                x: cursor.x,
+                y: cursor.y,
+                width: EDITOR_DEFAULT_GATE_WIDTH,
+                rotation: 0.0,
+            });
+            editor.selected = Some(level.gates.len() - 1);
+            edited = true;
+        }
+        editor.dragging = true;
+    }
+
+    let index = match editor.selected {
+        Some(index) if index < level.gates.len() => index,
+        _ => return edited,
+    };
+
+    if DELETE_KEYS
+        .iter()
+        .any(|key| keyboard_input.just_pressed(*key))
+    {
+        level.gates.remove(index);
+        editor.selected = None;
+        return true;
+    }
+
+    let gate = &mut level.gates[index];
+    if editor.dragging && (gate.x != cursor.x || gate.y != cursor.y) {
+        gate.x = cursor.x;
+        gate.y = cursor.y;
+        edited = true;
+    }
+
+    let resize = if keyboard_input.just_pressed(KeyCode::Up) {
+        EDITOR_WIDTH_STEP
+    } else if keyboard_input.just_pressed(KeyCode::Down) {
+        -EDITOR_WIDTH_STEP
+    } else {
+        0.0
+    };
+
+    if rotate != 0.0 || resize != 0.0 {
+        gate.rotation = (gate.rotation + rotate) % 180.0;
+        gate.width = (gate.width + resize).clamp(EDITOR_MIN_GATE_WIDTH, EDITOR_MAX_GATE_WIDTH);
+        edited = true;
+    }
+    edited
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_level(level: &mut level::Level) {
+    let file = level.file.clone().unwrap_or_else(|| {
+        level::level_directory().join(format!(
+            "{:}.{:}",
+            level.name.to_lowercase().replace(' ', "_"),
+            LEVEL_EXTENSION
+        ))
+    });
+
+    let result = ron::ser::to_string_pretty(level, ron::ser::PrettyConfig::new())
+        .map_err(|e| e.to_string())
+        .and_then(|source| std::fs::write(&file, source).map_err(|e| e.to_string()));
+
+    match result {
+        Ok(()) => {
+            info!("saved level to {:?}", file);
+            level.file = Some(file);
+        }
+        Err(e) => warn!("failed to save level to {:?}: {}", file, e),
+    }
+}
+
+// webではファイルに書けないので、ログに出す
+#[cfg(target_arch = "wasm32")]
+fn save_level(level: &mut level::Level) {
+    match ron::ser::to_string_pretty(level, ron::ser::PrettyConfig::new()) {
+        Ok(source) => info!("{}", source),
+        Err(e) => warn!("failed to serialize level: {}", e),
+    }
+}
+
+// 出発地点と向き、選択中の門の印
+fn marker_system(
+    editor: Res<Editor>,
+    levels: Res<level::Levels>,
+    mut start_query: Query<&mut Position, With<StartMarker>>,
+    mut direction_query: Query<&mut Position, With<DirectionMarker>>,
+    mut selection_query: Query<&mut Position, With<SelectionMarker>>,
+) {
+    let level = levels.current().filter(|_| editor.active);
+
+    for mut position in start_query.iter_mut() {
+        *position = match level {
+            Some(level) => level.start.position(),
+            None => Position::default(false),
+        };
+    }
+
+    for mut position in direction_query.iter_mut() {
+        *position = match level {
+            Some(level) => {
+                let mut position = level.start.position();
+                position.move_to_with_distance(level.start.direction(), HEAD_SIZE * 2.0);
+                position
+            }
+            None => Position::default(false),
+        };
+    }
+
+    for mut position in selection_query.iter_mut() {
+        *position = match level.and_then(|level| level.gates.get(editor.selected?)) {
+            Some(gate) => Position {
+                x: gate.x,
+                y: gate.y,
+                visible: true,
+            },
+            None => Position::default(false),
+        };
+    }
+}
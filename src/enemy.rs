@@ -0,0 +1,275 @@
+use crate::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::f32::consts::PI;
+
+pub struct ModPlugin;
+
+// 頭や尾を狙ってくる敵の生成と移動
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ModResources>()
+            .init_resource::<EnemiesInfo>()
+            .init_resource::<EnemyRng>()
+            .add_system_to_stage(
+                stage::UPDATE,
+                spawn_enemy_system.system().chain(void.system()),
+            )
+            .add_system(move_enemy_system.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_game_start.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_level_edited.system());
+    }
+}
+
+struct ModResources {
+    mesh: Handle<Mesh>,
+    seeker_material: Handle<StandardMaterial>,
+    patroller_material: Handle<StandardMaterial>,
+    bouncer_material: Handle<StandardMaterial>,
+}
+
+impl FromResources for ModResources {
+    fn from_resources(resources: &Resources) -> Self {
+        let mut meshes = resources.get_mut::<Assets<Mesh>>().unwrap();
+        let mut materials = resources.get_mut::<Assets<StandardMaterial>>().unwrap();
+
+        Self {
+            mesh: meshes.add(Mesh::from(shape::Icosphere {
+                radius: ENEMY_SIZE,
+                subdivisions: 1,
+            })),
+            seeker_material: materials.add(SEEKER_COLOR.into()),
+            patroller_material: materials.add(PATROLLER_COLOR.into()),
+            bouncer_material: materials.add(BOUNCER_COLOR.into()),
+        }
+    }
+}
+
+// 今回のゲームの開始時刻と、出現させた敵の数
+#[derive(Default)]
+pub struct EnemiesInfo {
+    started_at: f64,
+    count: usize,
+}
+
+// 敵の乱数。ゲームごとにシードから作り直すので、同じシードなら同じ順に同じ敵が出る
+pub struct EnemyRng(StdRng);
+
+impl Default for EnemyRng {
+    fn default() -> Self {
+        EnemyRng(StdRng::seed_from_u64(0))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum EnemyKind {
+    // 頭を追いかける
+    Seeker,
+    // 縦か横に往復し、近くに尾があれば向かっていく
+    Patroller,
+    // 斜めに進んで壁で跳ね返る
+    Bouncer,
+}
+
+pub struct Enemy {
+    pub kind: EnemyKind,
+    direction: Vec2,
+    speed: f32,
+}
+
+impl Enemy {
+    pub fn new(kind: EnemyKind, direction: Vec2, speed: f32) -> Self {
+        Self {
+            kind,
+            direction,
+            speed,
+        }
+    }
+}
+
+fn spawn_enemy_system(
+    commands: &mut Commands,
+    centipede_container: Res<CentipedeContainer>,
+    time: Res<clock::Clock>,
+    resources: Res<ModResources>,
+    mut enemies_info: ResMut<EnemiesInfo>,
+    mut enemy_rng: ResMut<EnemyRng>,
+    board: Res<Board>,
+    head_query: Query<&Position, With<head::Head>>,
+    enemy_query: Query<&Enemy>,
+) -> Option<()> {
+    let head_positions = head::positions(&centipede_container, &head_query);
+    if head_positions.is_empty() {
+        return None;
+    }
+
+    // 時間が経つほど数が増え、速くなる
+    let elapsed = time.seconds_since_startup() - enemies_info.started_at;
+    if (elapsed - ENEMY_FIRST_SPAWN_SECONDS) / ENEMY_SPAWN_PER_SECONDS <= enemies_info.count as f64
+        || enemy_query.iter().count() >= ENEMY_MAX_COUNT
+    {
+        return None;
+    }
+    enemies_info.count += 1;
+
+    // 一匹で使う乱数の数を揃えるため、置き場所探しは別の乱数で行う
+    let rng = &mut enemy_rng.0;
+    let (kind, material) = match rng.gen::<f32>() {
+        r if r < 0.3 => (EnemyKind::Seeker, resources.seeker_material.clone()),
+        r if r < 0.65 => (EnemyKind::Patroller, resources.patroller_material.clone()),
+        _ => (EnemyKind::Bouncer, resources.bouncer_material.clone()),
+    };
+
+    let direction = match kind {
+        EnemyKind::Patroller => match rng.gen::<u8>() % 4 {
+            0 => Vec2 { x: 1.0, y: 0.0 },
+            1 => Vec2 { x: -1.0, y: 0.0 },
+            2 => Vec2 { x: 0.0, y: 1.0 },
+            _ => Vec2 { x: 0.0, y: -1.0 },
+        },
+        _ => {
+            let radian = rng.gen::<f32>() * PI * 2.0;
+            Vec2 {
+                x: radian.cos(),
+                y: radian.sin(),
+            }
+        }
+    };
+    let mut position_rng = StdRng::seed_from_u64(rng.gen());
+    let position = enemy_position(&board, &head_positions, &mut position_rng)?;
+
+    commands
+        .spawn(PbrBundle {
+            mesh: resources.mesh.clone(),
+            material,
+            transform: Transform::from_translation(constants::INVISIBLE_POSITION),
+            global_transform: GlobalTransform::from_translation(constants::INVISIBLE_POSITION),
+            ..Default::default()
+        })
+        .with(Enemy::new(
+            kind,
+            direction,
+            ENEMY_SPEED + elapsed as f32 * ENEMY_SPEED_UP,
+        ))
+        .with(position);
+    None
+}
+
+// 頭から離れた置き場所。限られた回数で見つからなければNone
+fn enemy_position(
+    board: &Board,
+    head_positions: &[Position],
+    rng: &mut impl Rng,
+) -> Option<Position> {
+    (0..ENEMY_POSITION_ATTEMPTS)
+        .map(|_| Position {
+            x: (rng.gen::<f32>() - 0.5) * board.x_size(),
+            y: (rng.gen::<f32>() - 0.5) * board.y_size(),
+            visible: true,
+        })
+        .find(|position| {
+            head_positions
+                .iter()
+                .all(|head| head.distance(position) > ENEMY_NOT_SPAWN_DISTANCE_TO_HEAD)
+        })
+}
+
+fn move_enemy_system(
+    centipede_container: Res<CentipedeContainer>,
+    time: Res<clock::Clock>,
+    board: Res<Board>,
+    head_query: Query<&Position, With<head::Head>>,
+    tail_query: Query<&Position, With<tail::LivingTail>>,
+    mut enemy_query: Query<(&mut Enemy, &mut Position)>,
+) {
+    let head_positions = head::positions(&centipede_container, &head_query);
+
+    for (mut enemy, mut position) in enemy_query.iter_mut() {
+        let target = match enemy.kind {
+            // 一番近い頭を追う
+            EnemyKind::Seeker => head_positions
+                .iter()
+                .min_by(|a, b| position.distance(a).total_cmp(&position.distance(b)))
+                .copied(),
+            EnemyKind::Patroller => tail_query
+                .iter()
+                .filter(|tail| tail.visible)
+                .map(|tail| (position.distance(tail), *tail))
+                .filter(|(distance, _)| *distance <= PATROLLER_SIGHT)
+                .min_by(|a, b| a.0.total_cmp(&b.0))
+                .map(|(_, tail)| tail),
+            EnemyKind::Bouncer => None,
+        };
+
+        if let Some(target) = target {
+            enemy.direction = steer(
+                enemy.direction,
+                Vec2 {
+                    x: target.x - position.x,
+                    y: target.y - position.y,
+                },
+                ENEMY_TURN_SPEED * time.delta_seconds(),
+            );
+        }
+
+        // 壁の外に出ようとしたら跳ね返す
+        if position.x > board.x_border() && enemy.direction.x > 0.0
+            || position.x < -board.x_border() && enemy.direction.x < 0.0
+        {
+            enemy.direction.x = -enemy.direction.x;
+        }
+        if position.y > board.y_border() && enemy.direction.y > 0.0
+            || position.y < -board.y_border() && enemy.direction.y < 0.0
+        {
+            enemy.direction.y = -enemy.direction.y;
+        }
+
+        position.move_to_with_sec(enemy.direction, enemy.speed, time.delta_seconds());
+    }
+}
+
+// directionをtargetの方向へ、最大max_radianだけ回す
+fn steer(direction: Vec2, target: Vec2, max_radian: f32) -> Vec2 {
+    if target == (Vec2 { x: 0.0, y: 0.0 }) {
+        return direction;
+    }
+    let radian = (direction.x * target.y - direction.y * target.x)
+        .atan2(direction.x * target.x + direction.y * target.y)
+        .max(-max_radian)
+        .min(max_radian);
+    let (sin, cos) = radian.sin_cos();
+    Vec2 {
+        x: direction.x * cos - direction.y * sin,
+        y: direction.x * sin + direction.y * cos,
+    }
+}
+
+fn on_game_start(
+    commands: &mut Commands,
+    time: Res<clock::Clock>,
+    mut enemies_info: ResMut<EnemiesInfo>,
+    mut enemy_rng: ResMut<EnemyRng>,
+    (events, mut reader): (Res<Events<GameStart>>, Local<EventReader<GameStart>>),
+    query: Query<Entity, With<Enemy>>,
+) {
+    for event in reader.iter(&events) {
+        *enemy_rng = EnemyRng(event.rng(ENEMY_RNG_STREAM));
+        enemies_info.started_at = time.seconds_since_startup();
+        enemies_info.count = 0;
+        for entity in query.iter() {
+            commands.despawn_recursive(entity);
+        }
+    }
+}
+
+// エディタを使っている間は邪魔なので消す
+fn on_level_edited(
+    commands: &mut Commands,
+    (events, mut reader): (Res<Events<LevelEdited>>, Local<EventReader<LevelEdited>>),
+    query: Query<Entity, With<Enemy>>,
+) {
+    if reader.iter(&events).last().is_some() {
+        for entity in query.iter() {
+            commands.despawn_recursive(entity);
+        }
+    }
+}
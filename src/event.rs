@@ -11,58 +11,228 @@ impl Plugin for ModPlugin {
             .add_event::<CrushPoll>()
             .add_event::<ThroughGate>()
             .add_event::<EatTail>()
+            .add_event::<LevelEdited>()
+            .add_event::<DecayTail>()
+            .add_event::<ScoreAwarded>()
             .add_system_to_stage(stage::POST_UPDATE, game_start_system.system())
-            .add_system_to_stage(
-                stage::POST_UPDATE,
-                game_over_system.system().chain(void.system()),
-            );
+            .add_system_to_stage(stage::POST_UPDATE, game_over_system.system());
     }
 }
 
-// ゲーム開始
-pub struct GameStart {}
+// ゲーム開始。seedは今回のゲームの門や障害物、敵の乱数のシード
+pub struct GameStart {
+    pub seed: u64,
+}
 
-// 死亡
+impl GameStart {
+    // 使い道ごとに別の並びになる乱数。同じシードと使い道なら同じ並びになる
+    pub fn rng(&self, stream: u64) -> rand::rngs::StdRng {
+        rand::SeedableRng::seed_from_u64(self.seed ^ stream.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+}
+
+// 死亡。一匹ごとに送る。positionは死んだときの頭の位置
 pub struct GameOver {
+    pub player: PlayerId,
     pub head_entity: Entity,
+    pub cause: GameOverCause,
+    pub position: Position,
+    pub head: HeadFacts,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GameOverCause {
+    // 尾が無くなった
+    TailLost,
+    // 制限時間が来た
+    TimeUp,
+    // ステージの目標を達成した
+    Cleared,
+    // ステージやモードの変更、エディタで打ち切った
+    Aborted,
+    // 対戦で最後まで残った
+    Won,
+}
+
+// 出来事が起きたときの頭の様子。受け取る側が改めて調べなくて済むように、起きたときに詰めておく
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct HeadFacts {
+    pub speed: f32,
+    pub movement: Movement,
+    // 出来事の前と、出来事で増えたり減ったりした後の尾の数
+    pub tails_before: usize,
+    pub tails_after: usize,
+}
+
+impl HeadFacts {
+    pub fn new(centipede: &Alive, tails_after: usize) -> Self {
+        Self {
+            speed: centipede.speed,
+            movement: centipede.movement,
+            tails_before: centipede.tail_count,
+            tails_after,
+        }
+    }
+}
+
+// くぐったりぶつかったりした門。widthは柱の間の長さ、rotationは回転(度)でGateSpecと同じ
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct GateFacts {
+    pub entity: Entity,
+    pub width: f32,
+    pub rotation: f32,
+}
+
+impl GateFacts {
+    // 両脇の柱の位置から。一本目が右、二本目が左に置かれる
+    pub fn new(entity: Entity, poll1: &Position, poll2: &Position) -> Self {
+        Self {
+            entity,
+            width: poll1.distance(poll2),
+            rotation: (poll1.y - poll2.y).atan2(poll1.x - poll2.x).to_degrees(),
+        }
+    }
 }
 
-// 門の脇の柱に激突
-pub struct CrushPoll {}
+// 柱に激突したのと同じ扱いになる、ぶつかったもの
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CrushCause {
+    Poll,
+    Head,
+    Enemy,
+    Asteroid,
+}
 
-// 門を通過
-pub struct ThroughGate {}
+// 門の脇の柱に激突。頭同士、敵、小惑星にぶつかったときも同じ。positionはぶつかった頭の位置
+// gateは柱にぶつかったときだけ
+#[derive(Clone, Debug)]
+pub struct CrushPoll {
+    pub player: PlayerId,
+    pub cause: CrushCause,
+    pub gate: Option<GateFacts>,
+    pub position: Position,
+    pub head: HeadFacts,
+}
 
-// 尾にぶつかる
+// 門を通過。positionは門を横切った位置
+#[derive(Clone, Debug)]
+pub struct ThroughGate {
+    pub player: PlayerId,
+    pub gate: GateFacts,
+    pub position: Position,
+    pub head: HeadFacts,
+}
+
+// playerの尾にぶつかる。eaterはぶつかった頭の持ち主で、敵ならNone。positionは切れた尾の位置
+// tails_beforeは切れる前のplayerの尾の数。切れた後はtail_indexになる
+#[derive(Clone, Debug)]
 pub struct EatTail {
+    pub player: PlayerId,
     pub tail_index: usize,
+    pub eater: Option<PlayerId>,
+    pub position: Position,
+    pub tails_before: usize,
+}
+
+// 時間経過で尾が一つ落ちる
+pub struct DecayTail {
+    pub player: PlayerId,
+}
+
+// 点が動いた。positionは点が付いた場所(門を横切った位置など)
+// speedは門をくぐって付いた点のときだけ、くぐったときの速さ
+#[derive(Clone, Debug)]
+pub struct ScoreAwarded {
+    pub player: PlayerId,
+    pub points: isize,
+    pub reason: ScoreReason,
+    pub position: Position,
+    pub speed: Option<f32>,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ScoreReason {
+    // 門をくぐった
+    Gate,
+    // 門をくぐったときの速さの上乗せ
+    Speed,
+    // 対戦で相手の尾を奪った
+    Steal,
+    // ブレーキの支払い
+    Brake,
 }
 
-// 尾が無くなったら終わり
+impl ScoreReason {
+    // 内訳を並べる順
+    pub const ALL: [ScoreReason; 4] = [
+        ScoreReason::Gate,
+        ScoreReason::Speed,
+        ScoreReason::Steal,
+        ScoreReason::Brake,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScoreReason::Gate => "GATE",
+            ScoreReason::Speed => "SPEED",
+            ScoreReason::Steal => "STEAL",
+            ScoreReason::Brake => "BRAKE",
+        }
+    }
+}
+
+// エディタでステージが書き換えられた
+pub struct LevelEdited {}
+
+// 尾が無くなったら、その一匹は終わり
 fn game_over_system(
-    time: Res<Time>,
+    time: Res<clock::Clock>,
+    session: Res<mode::Session>,
     mut centipede_container: ResMut<CentipedeContainer>,
     mut game_over_events: ResMut<Events<GameOver>>,
-) -> Option<()> {
-    let centipede = centipede_container.alive()?;
-    if centipede.tail_count <= 0 {
-        game_over_events.send(GameOver {
-            head_entity: centipede.head_entity,
-        });
-        centipede_container.centipede = Centipede::Dead(time.seconds_since_startup());
+) {
+    if !session.mode.ends_without_tail() {
+        return;
+    }
+
+    let lost: Vec<_> = centipede_container
+        .iter_alive()
+        .filter(|centipede| centipede.tail_count == 0)
+        .map(|centipede| centipede.player)
+        .collect();
+    for player in lost {
+        centipede_container.kill(
+            player,
+            time.seconds_since_startup(),
+            GameOverCause::TailLost,
+            &mut game_over_events,
+        );
     }
-    None
 }
 
 // ゲームが終わって規定の時間が経ったら、再開。起動時の処理も同じ
 fn game_start_system(
-    time: Res<Time>,
+    time: Res<clock::Clock>,
     centipede_container: Res<CentipedeContainer>,
+    editor: Res<editor::Editor>,
+    session: Res<mode::Session>,
     mut game_start_events: ResMut<Events<event::GameStart>>,
 ) {
-    if let Centipede::Dead(dead_at) = centipede_container.centipede {
-        if dead_at == 0.0 || dead_at < time.seconds_since_startup() - 2.0 {
-            game_start_events.send(event::GameStart {});
+    // エディタを使っている間は始めない
+    if editor.active {
+        return;
+    }
+
+    // 全員が死んでから規定の時間が経ったら
+    if let Some(dead_at) = centipede_container.dead_at() {
+        if dead_at == 0.0 || dead_at < time.seconds_since_startup() - session.mode.restart_delay() {
+            // シードを固定していれば同じ門の並びでやり直す
+            let seed = if session.seed_locked {
+                session.seed
+            } else {
+                rand::random::<u32>() as u64
+            };
+            game_start_events.send(event::GameStart { seed });
         }
     }
 }
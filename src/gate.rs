@@ -1,5 +1,6 @@
 use crate::*;
-use rand::prelude::random;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
 use std::f32::consts::PI;
 
 pub struct ModPlugin {}
@@ -8,11 +9,16 @@ impl Plugin for ModPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<ModResources>()
             .init_resource::<GatesInfo>()
+            .init_resource::<GateRng>()
+            .init_resource::<director::Director>()
             .add_system_to_stage(
                 stage::UPDATE,
                 spawn_gate_system.system().chain(void.system()),
             )
-            .add_system_to_stage(stage::RECEIVE_EVENT, on_game_start.system());
+            .add_system_to_stage(stage::UPDATE, scripted_gate_system.system())
+            .add_system(move_gate_system.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_game_start.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_level_edited.system());
     }
 }
 
@@ -21,6 +27,8 @@ struct ModResources {
     poll_material: Handle<StandardMaterial>,
     bar_mesh: Handle<Mesh>,
     bar_material: Handle<StandardMaterial>,
+    spinning_bar_material: Handle<StandardMaterial>,
+    drifting_bar_material: Handle<StandardMaterial>,
 }
 
 impl FromResources for ModResources {
@@ -36,72 +44,260 @@ impl FromResources for ModResources {
             poll_material: materials.add(POLL_COLOR.into()),
             bar_mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
             bar_material: materials.add(BAR_COLOR.into()),
+            spinning_bar_material: materials.add(SPINNING_BAR_COLOR.into()),
+            drifting_bar_material: materials.add(DRIFTING_BAR_COLOR.into()),
         }
     }
 }
 
+// 今回のゲームの開始時刻と、次にランダムな門を出す経過秒数、ステージの台本で出した数
+// scheduled_atは次の門を間隔どおりに出したときの経過秒数。門の幅や種類はこの時刻で決める
+// pendingは決めたが、頭が近くてまだ出していない門
 #[derive(Default)]
 pub struct GatesInfo {
-    count: usize,
+    started_at: f64,
+    next_spawn_at: f32,
+    scheduled_at: f32,
+    scripted: usize,
+    pending: Option<RolledGate>,
 }
 
-pub struct Gate {}
+// 乱数で決めた門
+struct RolledGate {
+    kind: GateKind,
+    length: f32,
+    rotation: f32,
+    drift: f32,
+    position: Position,
+}
+
+// 門の乱数。ゲームごとにシードから作り直すので、同じシードなら同じ順に同じ門が出る
+pub struct GateRng(StdRng);
+
+impl Default for GateRng {
+    fn default() -> Self {
+        GateRng(StdRng::seed_from_u64(0))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum GateKind {
+    Normal,
+    // その場で回る
+    Spinning,
+    // ゆっくり流れて、壁で跳ね返る
+    Drifting,
+}
+
+pub struct Gate {
+    pub kind: GateKind,
+    drift: Vec2,
+}
 
 pub struct Poll {}
 
 pub struct Bar {}
 
+// ランダムな門。間隔と幅と種類はdirectorに従う
 fn spawn_gate_system(
     commands: &mut Commands,
     centipede_container: Res<CentipedeContainer>,
-    time: Res<Time>,
+    time: Res<clock::Clock>,
     resources: Res<ModResources>,
     mut gates_info: ResMut<GatesInfo>,
+    mut gate_rng: ResMut<GateRng>,
+    board: Res<Board>,
+    obstacles: Res<obstacle::Obstacles>,
+    levels: Res<level::Levels>,
+    default_director: Res<director::Director>,
     head_query: Query<&Position, With<head::Head>>,
+    gate_query: Query<&Gate>,
 ) -> Option<()> {
-    let head_position = head_query.get(centipede_container.head_entity()?).ok()?;
-
-    if time.seconds_since_startup() / GATE_SPAWN_PER_SECONDS > gates_info.count as f64 {
-        gates_info.count += 1;
-        let length = GATE_MIN_WIDTH + random::<f32>() * (GATE_MAX_WIDTH - GATE_MIN_WIDTH);
-        let position = gate_position(length, head_position);
-
-        let gate = commands
-            .spawn(ContainerBundle {
-                transform: Transform {
-                    translation: constants::INVISIBLE_POSITION,
-                    rotation: Quat::from_rotation_z(random::<f32>() * PI),
-                    ..Default::default()
-                },
+    let head_positions = head::positions(&centipede_container, &head_query);
+    if head_positions.is_empty() {
+        return None;
+    }
+
+    // ステージ選択中は、ステージが許したときだけランダムに出す
+    if !levels.current().map_or(true, |level| level.random_gates) {
+        return None;
+    }
+    let director = levels
+        .current()
+        .and_then(|level| level.director.as_ref())
+        .unwrap_or(&default_director);
+
+    if gates_info.pending.is_none() {
+        let elapsed = (time.seconds_since_startup() - gates_info.started_at) as f32;
+        let gate_count = gate_query.iter().count();
+        if gate_count >= director.max_gates
+            || elapsed < gates_info.next_spawn_at && gate_count >= director.min_gates
+        {
+            return None;
+        }
+        gates_info.next_spawn_at = elapsed + director.interval(elapsed);
+
+        // 門が減ると早めに出すので、実際に出す時刻で決めると遊び方でn番目の門が変わってしまう
+        // 幅と種類は予定の時刻で決め、同じシードならn番目の門はいつも同じにする
+        let scheduled_at = gates_info.scheduled_at;
+        gates_info.scheduled_at += director.interval(scheduled_at);
+
+        // 一つの門で使う乱数の数を揃えるため、置き場所探しは別の乱数で行う
+        let rng = &mut gate_rng.0;
+        let kind = director.kind(scheduled_at, rng);
+        let length = director.width(scheduled_at, rng);
+        let rotation = rng.gen::<f32>() * PI;
+        let drift = rng.gen::<f32>() * PI * 2.0;
+        let mut position_rng = StdRng::seed_from_u64(rng.gen());
+        let position = gate_position(&board, length, rotation, &obstacles, &mut position_rng)?;
+        gates_info.pending = Some(RolledGate {
+            kind,
+            length,
+            rotation,
+            drift,
+            position,
+        });
+    }
+
+    // 頭の近くには出さずに、離れるまで待つ。置き場所は頭で変えないので、同じシードなら同じ場所に出る
+    let gate = gates_info.pending.as_ref()?;
+    if head_positions
+        .iter()
+        .any(|head| head.distance(&gate.position) <= GATE_NOT_SPAWN_DISTANCE_TO_HEAD)
+    {
+        return None;
+    }
+    let gate = gates_info.pending.take()?;
+    spawn_gate(
+        commands,
+        &resources,
+        gate.kind,
+        gate.position,
+        gate.length,
+        gate.rotation,
+        gate.drift,
+    );
+    None
+}
+
+// 回る門と流れる門を動かす
+fn move_gate_system(
+    time: Res<clock::Clock>,
+    board: Res<Board>,
+    mut query: Query<(&mut Gate, &mut Position, &mut Transform)>,
+) {
+    for (mut gate, mut position, mut transform) in query.iter_mut() {
+        match gate.kind {
+            GateKind::Normal => {}
+            GateKind::Spinning => {
+                transform.rotation *= Quat::from_rotation_z(GATE_SPIN_SPEED * time.delta_seconds());
+            }
+            GateKind::Drifting => {
+                if position.x > board.x_border() && gate.drift.x > 0.0
+                    || position.x < -board.x_border() && gate.drift.x < 0.0
+                {
+                    gate.drift.x = -gate.drift.x;
+                }
+                if position.y > board.y_border() && gate.drift.y > 0.0
+                    || position.y < -board.y_border() && gate.drift.y < 0.0
+                {
+                    gate.drift.y = -gate.drift.y;
+                }
+                position.move_to_with_sec(gate.drift, GATE_DRIFT_SPEED, time.delta_seconds());
+            }
+        }
+    }
+}
+
+// ステージの台本に書かれた時刻になった門を出す
+fn scripted_gate_system(
+    commands: &mut Commands,
+    centipede_container: Res<CentipedeContainer>,
+    time: Res<clock::Clock>,
+    resources: Res<ModResources>,
+    levels: Res<level::Levels>,
+    mut gates_info: ResMut<GatesInfo>,
+) {
+    if let (Some(level), true) = (levels.current(), centipede_container.playing()) {
+        let elapsed = time.seconds_since_startup() - gates_info.started_at;
+        while let Some(spawn) = level.spawns.get(gates_info.scripted) {
+            if spawn.at > elapsed {
+                break;
+            }
+            gates_info.scripted += 1;
+            spawn_gate_spec(commands, &resources, &spawn.gate);
+        }
+    }
+}
+
+fn spawn_gate_spec(commands: &mut Commands, resources: &Res<ModResources>, spec: &level::GateSpec) {
+    spawn_gate(
+        commands,
+        resources,
+        GateKind::Normal,
+        Position {
+            x: spec.x,
+            y: spec.y,
+            visible: true,
+        },
+        spec.width,
+        spec.rotation.to_radians(),
+        0.0,
+    );
+}
+
+fn spawn_gate(
+    commands: &mut Commands,
+    resources: &Res<ModResources>,
+    kind: GateKind,
+    position: Position,
+    length: f32,
+    rotation: f32,
+    drift: f32,
+) {
+    let drift = Vec2 {
+        x: drift.cos(),
+        y: drift.sin(),
+    };
+
+    let gate = commands
+        .spawn(ContainerBundle {
+            transform: Transform {
+                translation: constants::INVISIBLE_POSITION,
+                rotation: Quat::from_rotation_z(rotation),
                 ..Default::default()
-            })
-            .with(Gate {})
-            .with(position)
-            .current_entity()
-            .unwrap();
-
-        spawn_poll(commands, &resources, gate, length);
-        spawn_poll(commands, &resources, gate, -length);
-        // childrenはpositionもってないので、transformationから算出する
-
-        commands
-            .spawn(PbrBundle {
-                mesh: resources.bar_mesh.clone(),
-                material: resources.bar_material.clone(),
-                transform: Transform {
-                    scale: Vec3 {
-                        x: length,
-                        y: BAR_DIAMETER,
-                        z: BAR_DIAMETER,
-                    },
-                    ..Default::default()
+            },
+            ..Default::default()
+        })
+        .with(Gate { kind, drift })
+        .with(position)
+        .current_entity()
+        .unwrap();
+
+    spawn_poll(commands, resources, gate, length);
+    spawn_poll(commands, resources, gate, -length);
+    // childrenはpositionもってないので、transformationから算出する
+
+    commands
+        .spawn(PbrBundle {
+            mesh: resources.bar_mesh.clone(),
+            material: match kind {
+                GateKind::Normal => resources.bar_material.clone(),
+                GateKind::Spinning => resources.spinning_bar_material.clone(),
+                GateKind::Drifting => resources.drifting_bar_material.clone(),
+            },
+            transform: Transform {
+                scale: Vec3 {
+                    x: length,
+                    y: BAR_DIAMETER,
+                    z: BAR_DIAMETER,
                 },
                 ..Default::default()
-            })
-            .with(Bar {})
-            .with(Parent(gate));
-    }
-    None
+            },
+            ..Default::default()
+        })
+        .with(Bar {})
+        .with(Parent(gate));
 }
 
 fn spawn_poll(commands: &mut Commands, resources: &Res<ModResources>, gate: Entity, length: f32) {
@@ -122,30 +318,91 @@ fn spawn_poll(commands: &mut Commands, resources: &Res<ModResources>, gate: Enti
         .with(Parent(gate));
 }
 
-fn gate_position(length: f32, head_position: &Position) -> Position {
-    loop {
-        let position = Position {
-            x: random::<f32>() * (constants::BOARD_X_SIZE - length)
-                - (constants::BOARD_X_BORDER - length / 2.0),
-            y: random::<f32>() * (constants::BOARD_Y_SIZE - length)
-                - (constants::BOARD_Y_BORDER - length / 2.0),
+// 障害物を置いたパネルにかからない置き場所。限られた回数で見つからなければNone
+fn gate_position(
+    board: &Board,
+    length: f32,
+    rotation: f32,
+    obstacles: &obstacle::Obstacles,
+    rng: &mut impl Rng,
+) -> Option<Position> {
+    // 門が盤面より長ければ真ん中にしか置けない
+    let x_range = (board.x_size() - length).max(0.0);
+    let y_range = (board.y_size() - length).max(0.0);
+    (0..GATE_POSITION_ATTEMPTS)
+        .map(|_| Position {
+            x: (rng.gen::<f32>() - 0.5) * x_range,
+            y: (rng.gen::<f32>() - 0.5) * y_range,
             visible: true,
-        };
+        })
+        .find(|position| !on_obstacle(position, length, rotation, obstacles))
+}
 
-        if head_position.distance(&position) > GATE_NOT_SPAWN_DISTANCE_TO_HEAD {
-            return position;
-        }
-    }
+// 柱と棒のどこかが障害物を置いたパネルにかかるか。壊れた小惑星の跡にも置かない
+fn on_obstacle(
+    position: &Position,
+    length: f32,
+    rotation: f32,
+    obstacles: &obstacle::Obstacles,
+) -> bool {
+    let steps = (length / (PANEL_SIZE / 4.0)).ceil() as usize;
+    (0..=steps).any(|step| {
+        let offset = length * (step as f32 / steps as f32 - 0.5);
+        obstacles.laid_on(&Position {
+            x: position.x + rotation.cos() * offset,
+            y: position.y + rotation.sin() * offset,
+            visible: true,
+        })
+    })
 }
 
 fn on_game_start(
     commands: &mut Commands,
+    time: Res<clock::Clock>,
+    resources: Res<ModResources>,
+    levels: Res<level::Levels>,
+    mut gates_info: ResMut<GatesInfo>,
+    mut gate_rng: ResMut<GateRng>,
     (events, mut reader): (Res<Events<GameStart>>, Local<EventReader<GameStart>>),
     query: Query<Entity, With<Gate>>,
 ) {
-    for _ in reader.iter(&events) {
-        for entity in query.iter() {
-            commands.despawn_recursive(entity);
+    for event in reader.iter(&events) {
+        *gate_rng = GateRng(event.rng(GATE_RNG_STREAM));
+        *gates_info = GatesInfo {
+            started_at: time.seconds_since_startup(),
+            ..Default::default()
+        };
+        place_level_gates(commands, &resources, &levels, &query);
+    }
+}
+
+// エディタで書き換えられたら置き直す
+fn on_level_edited(
+    commands: &mut Commands,
+    resources: Res<ModResources>,
+    levels: Res<level::Levels>,
+    (events, mut reader): (Res<Events<LevelEdited>>, Local<EventReader<LevelEdited>>),
+    query: Query<Entity, With<Gate>>,
+) {
+    if reader.iter(&events).last().is_some() {
+        place_level_gates(commands, &resources, &levels, &query);
+    }
+}
+
+// 今ある門を消して、ステージに最初から置かれている門を置く
+fn place_level_gates(
+    commands: &mut Commands,
+    resources: &Res<ModResources>,
+    levels: &level::Levels,
+    query: &Query<Entity, With<Gate>>,
+) {
+    for entity in query.iter() {
+        commands.despawn_recursive(entity);
+    }
+
+    if let Some(level) = levels.current() {
+        for spec in &level.gates {
+            spawn_gate_spec(commands, resources, spec);
         }
     }
 }
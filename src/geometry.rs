@@ -0,0 +1,127 @@
+use crate::*;
+
+// 当たり判定のための図形の計算
+// 浮動小数点の誤差があっても、接する、重なる、長さ0の場合の答えが決まるように、GEOMETRY_EPSILON以内は同じ点とみなす
+
+fn cross(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// 線分a-bを通る直線からpointまでの距離。左側が正。a-bが点ならNone
+pub fn signed_distance(point: &Position, a: &Position, b: &Position) -> Option<f32> {
+    let ab = Vec2::from(*b) - Vec2::from(*a);
+    let length = ab.length();
+    if length <= GEOMETRY_EPSILON {
+        return None;
+    }
+    Some(cross(ab, Vec2::from(*point) - Vec2::from(*a)) / length)
+}
+
+// 線分a-b上でpointに一番近い点。a-bが点ならa
+pub fn closest_point(point: &Position, a: &Position, b: &Position) -> Position {
+    let ab = Vec2::from(*b) - Vec2::from(*a);
+    let length_squared = ab.dot(ab);
+    if length_squared <= GEOMETRY_EPSILON * GEOMETRY_EPSILON {
+        return *a;
+    }
+    let ratio = ((Vec2::from(*point) - Vec2::from(*a)).dot(ab) / length_squared).clamp(0.0, 1.0);
+    let closest = Vec2::from(*a) + ab * ratio;
+    Position {
+        x: closest.x,
+        y: closest.y,
+        visible: a.visible,
+    }
+}
+
+// fromからtoへratio(0.0〜1.0)だけ進んだ点
+pub fn point_along(from: &Position, to: &Position, ratio: f32) -> Position {
+    let point = Vec2::from(*from).lerp(Vec2::from(*to), ratio);
+    Position {
+        x: point.x,
+        y: point.y,
+        visible: to.visible,
+    }
+}
+
+pub fn distance_to_segment(point: &Position, a: &Position, b: &Position) -> f32 {
+    point.distance(&closest_point(point, a, b))
+}
+
+// 中心の距離がreach以内か。ちょうど接している場合も含む
+pub fn touching(a: &Position, b: &Position, reach: f32) -> bool {
+    a.distance(b) <= reach + GEOMETRY_EPSILON
+}
+
+// fromからtoへ動いたときに、線分a-bを端以外のところで横切ったら、動いた割合(0.0〜1.0)
+// 線の上はa-bの左側とみなすので、線の上で止まってから抜けても一度だけ数える
+// 端を通ったかどうかは柱の当たり判定に任せる
+pub fn crossing(from: &Position, to: &Position, a: &Position, b: &Position) -> Option<f32> {
+    let (from_distance, to_distance) = (signed_distance(from, a, b)?, signed_distance(to, a, b)?);
+    let right = |distance: f32| distance < -GEOMETRY_EPSILON;
+    if right(from_distance) == right(to_distance) {
+        return None;
+    }
+
+    // 横切った点が、両端からGEOMETRY_EPSILONより内側にあるか
+    let ratio = from_distance / (from_distance - to_distance);
+    let point = Vec2::from(*from).lerp(Vec2::from(*to), ratio);
+    let ab = Vec2::from(*b) - Vec2::from(*a);
+    let length = ab.length();
+    let along = (point - Vec2::from(*a)).dot(ab) / length;
+    if along > GEOMETRY_EPSILON && along < length - GEOMETRY_EPSILON {
+        Some(ratio.clamp(0.0, 1.0))
+    } else {
+        None
+    }
+}
+
+// fromからtoへ動く点が、centerからreach以内に最初に入る割合(0.0〜1.0)。入らなければNone
+// 動く円と止まった円なら、reachに半径の和を渡す
+pub fn sweep(from: &Position, to: &Position, center: &Position, reach: f32) -> Option<f32> {
+    if touching(from, center, reach) {
+        return Some(0.0);
+    }
+
+    let movement = Vec2::from(*to) - Vec2::from(*from);
+    let offset = Vec2::from(*from) - Vec2::from(*center);
+    let a = movement.dot(movement);
+    if a <= GEOMETRY_EPSILON * GEOMETRY_EPSILON {
+        return None;
+    }
+    let reach = reach + GEOMETRY_EPSILON;
+    let half_b = offset.dot(movement);
+    let c = offset.dot(offset) - reach * reach;
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let ratio = (-half_b - discriminant.sqrt()) / a;
+    if (0.0..=1.0).contains(&ratio) {
+        Some(ratio)
+    } else {
+        None
+    }
+}
+
+// 二つの点が同じ刻みの間にそれぞれ動いたとき、reach以内に最初に入る割合
+// 片方から見たもう片方の動きに直して、sweepで調べる
+pub fn sweep_pair(
+    from: &Position,
+    to: &Position,
+    other_from: &Position,
+    other_to: &Position,
+    reach: f32,
+) -> Option<f32> {
+    let relative = |a: &Position, b: &Position| Position {
+        x: a.x - b.x,
+        y: a.y - b.y,
+        visible: a.visible,
+    };
+    sweep(
+        &relative(from, other_from),
+        &relative(to, other_to),
+        &Position::default(true),
+        reach,
+    )
+}
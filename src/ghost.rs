@@ -0,0 +1,327 @@
+use crate::*;
+use std::collections::HashMap;
+
+pub struct ModPlugin;
+
+// 一番良かった走りを記録して、同じシードで遊ぶときに半透明の幽霊として並走させる
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ModResources>()
+            .init_resource::<Ghosts>()
+            .add_system_to_stage(stage::POST_UPDATE, record_system.system())
+            .add_system_to_stage(stage::POST_UPDATE, replay_system.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_game_start.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_through_gate.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_game_over.system());
+    }
+}
+
+struct ModResources {
+    head_mesh: Handle<Mesh>,
+    tail_mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromResources for ModResources {
+    fn from_resources(resources: &Resources) -> Self {
+        let mut meshes = resources.get_mut::<Assets<Mesh>>().unwrap();
+        let mut materials = resources.get_mut::<Assets<StandardMaterial>>().unwrap();
+        Self {
+            head_mesh: meshes.add(Mesh::from(shape::Icosphere {
+                radius: HEAD_SIZE,
+                subdivisions: 5,
+            })),
+            tail_mesh: meshes.add(Mesh::from(shape::Cube { size: TAIL_SIZE })),
+            material: materials.add(GHOST_COLOR.into()),
+        }
+    }
+}
+
+// 開始からの秒数ごとの頭の位置と尾の長さ
+#[derive(Copy, Clone, Debug)]
+struct Sample {
+    at: f64,
+    position: Position,
+    tail_count: usize,
+}
+
+// 一回分の走り。gate_timesは門をくぐった時刻(開始からの秒数)
+#[derive(Clone, Default, Debug)]
+pub struct Run {
+    pub record: leaderboard::Record,
+    samples: Vec<Sample>,
+    pub gate_times: Vec<f64>,
+}
+
+impl Run {
+    // atの時点の頭の位置と尾の長さ。終わっていればNone
+    fn at(&self, at: f64) -> Option<(usize, Position, usize)> {
+        let index = self.samples.partition_point(|sample| sample.at <= at);
+        let (before, after) = (
+            self.samples.get(index.checked_sub(1)?)?,
+            self.samples.get(index)?,
+        );
+        let ratio = ((at - before.at) / (after.at - before.at)) as f32;
+        Some((
+            index,
+            Position {
+                x: before.position.x + (after.position.x - before.position.x) * ratio,
+                y: before.position.y + (after.position.y - before.position.y) * ratio,
+                visible: true,
+            },
+            before.tail_count,
+        ))
+    }
+
+    fn max_tail_count(&self) -> usize {
+        self.samples
+            .iter()
+            .map(|sample| sample.tail_count)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+// 遊び方、ステージ、シードごとの一番良い走りと、今記録している走り
+#[derive(Default)]
+pub struct Ghosts {
+    best: HashMap<(leaderboard::BoardKey, u64), Run>,
+    recording: Option<Run>,
+    // 今回並走している走りと、くぐった門の数
+    replaying: Option<Run>,
+    gates: usize,
+    // 直前にくぐった門での、幽霊との差(秒、プラスなら遅れ)と、その時刻
+    pub last_delta: Option<(f64, f64)>,
+}
+
+impl Ghosts {
+    // 覚えている走りの数
+    pub fn runs(&self) -> usize {
+        self.best.len()
+    }
+}
+
+// 幽霊の頭と尾の両方に付ける
+struct Ghost {}
+
+struct GhostHead {}
+
+struct GhostTail {
+    index: usize,
+}
+
+// 一人で遊んでいるときだけ、一人目の走りを記録する
+fn record_system(
+    time: Res<clock::Clock>,
+    session: Res<mode::Session>,
+    centipede_container: Res<CentipedeContainer>,
+    mut ghosts: ResMut<Ghosts>,
+    head_query: Query<&Position, With<head::Head>>,
+) {
+    let recording = match ghosts.recording.as_mut() {
+        Some(recording) => recording,
+        None => return,
+    };
+    let centipede = match centipede_container.alive(PlayerId(0)) {
+        Some(centipede) => centipede,
+        None => return,
+    };
+
+    let at = session.elapsed(&time);
+    if matches!(recording.samples.last(), Some(last) if at - last.at < GHOST_SAMPLE_SECONDS) {
+        return;
+    }
+    if let Ok(position) = head_query.get(centipede.head_entity) {
+        recording.samples.push(Sample {
+            at,
+            position: *position,
+            tail_count: centipede.tail_count,
+        });
+    }
+}
+
+// 幽霊の頭を記録通りに動かし、尾はその跡に並べる
+fn replay_system(
+    time: Res<clock::Clock>,
+    session: Res<mode::Session>,
+    ghosts: Res<Ghosts>,
+    mut head_query: Query<&mut Position, With<GhostHead>>,
+    mut tail_query: Query<(&mut Position, &GhostTail)>,
+) {
+    let run = match ghosts.replaying.as_ref() {
+        Some(run) => run,
+        None => return,
+    };
+    let current = run.at(session.elapsed(&time));
+
+    for mut position in head_query.iter_mut() {
+        *position = current.map_or(Position::default(false), |(_, head, _)| head);
+    }
+
+    let mut tail_positions = vec![];
+    if let Some((index, head, tail_count)) = current {
+        let mut prev = head;
+        let mut distance = 0.0;
+        'outer: for sample in run.samples[..index].iter().rev() {
+            let current_distance = sample.position.distance(&prev);
+            distance += current_distance;
+            while distance >= TAIL_DISTANCE {
+                distance -= TAIL_DISTANCE;
+                tail_positions.push(prev.forward_to(&sample.position, current_distance - distance));
+                if tail_positions.len() >= tail_count {
+                    break 'outer;
+                }
+            }
+            prev = sample.position;
+        }
+    }
+
+    for (mut position, tail) in tail_query.iter_mut() {
+        *position = tail_positions
+            .get(tail.index)
+            .copied()
+            .unwrap_or_else(|| Position::default(false));
+    }
+}
+
+// 記録を始め、同じシードの一番良い走りがあれば幽霊を出す
+fn on_game_start(
+    commands: &mut Commands,
+    resources: Res<ModResources>,
+    session: Res<mode::Session>,
+    levels: Res<level::Levels>,
+    attract: Res<title::Attract>,
+    centipede_container: Res<CentipedeContainer>,
+    mut ghosts: ResMut<Ghosts>,
+    (events, mut reader): (Res<Events<GameStart>>, Local<EventReader<GameStart>>),
+    query: Query<Entity, With<Ghost>>,
+) {
+    for event in reader.iter(&events) {
+        for entity in query.iter() {
+            commands.despawn(entity);
+        }
+        ghosts.gates = 0;
+        ghosts.last_delta = None;
+
+        let solo = !attract.active && centipede_container.centipedes.len() == 1;
+        ghosts.recording = if solo { Some(Run::default()) } else { None };
+        ghosts.replaying = if solo {
+            ghosts
+                .best
+                .get(&(
+                    leaderboard::BoardKey::current(&session, &levels),
+                    event.seed,
+                ))
+                .cloned()
+        } else {
+            None
+        };
+
+        if let Some(run) = ghosts.replaying.as_ref() {
+            spawn_ghost(commands, &resources, resources.head_mesh.clone()).with(GhostHead {});
+            for index in 0..run.max_tail_count() {
+                spawn_ghost(commands, &resources, resources.tail_mesh.clone())
+                    .with(GhostTail { index });
+            }
+        }
+    }
+}
+
+// 当たり判定のための部品を持たないので、何にもぶつからない
+fn spawn_ghost<'a>(
+    commands: &'a mut Commands,
+    resources: &ModResources,
+    mesh: Handle<Mesh>,
+) -> &'a mut Commands {
+    commands
+        .spawn(PbrBundle {
+            mesh,
+            material: resources.material.clone(),
+            visible: Visible {
+                is_transparent: true,
+                ..Default::default()
+            },
+            transform: Transform::from_translation(INVISIBLE_POSITION),
+            ..Default::default()
+        })
+        .with(Ghost {})
+        .with(Position::default(false))
+}
+
+// 門をくぐるたびに、幽霊が同じ数だけくぐった時刻と比べる
+fn on_through_gate(
+    time: Res<clock::Clock>,
+    session: Res<mode::Session>,
+    mut ghosts: ResMut<Ghosts>,
+    (events, mut reader): (Res<Events<ThroughGate>>, Local<EventReader<ThroughGate>>),
+) {
+    for event in reader.iter(&events) {
+        if event.player != PlayerId(0) {
+            continue;
+        }
+        let at = session.elapsed(&time);
+        if let Some(recording) = ghosts.recording.as_mut() {
+            recording.gate_times.push(at);
+        }
+
+        let gates = ghosts.gates;
+        ghosts.gates += 1;
+        if let Some(ghost_at) = ghosts
+            .replaying
+            .as_ref()
+            .and_then(|run| run.gate_times.get(gates).copied())
+        {
+            ghosts.last_delta = Some((at - ghost_at, time.seconds_since_startup()));
+        }
+    }
+}
+
+// 記録を残すゲームで一番良い走りなら、そのシードの幽霊にする
+fn on_game_over(
+    session: Res<mode::Session>,
+    levels: Res<level::Levels>,
+    status: Res<ui::Status>,
+    mut ghosts: ResMut<Ghosts>,
+    (events, mut reader): (Res<Events<GameOver>>, Local<EventReader<GameOver>>),
+) {
+    for event in reader.iter(&events) {
+        if event.player != PlayerId(0) {
+            continue;
+        }
+        let mut run = match ghosts.recording.take() {
+            Some(run) => run,
+            None => continue,
+        };
+        if event.cause == GameOverCause::Aborted || !session.mode.records_score() {
+            continue;
+        }
+
+        let player_status = status.get(event.player);
+        run.record = leaderboard::Record {
+            score: player_status.score,
+            gates: player_status.gates,
+        };
+        let key = (
+            leaderboard::BoardKey::current(&session, &levels),
+            session.seed,
+        );
+        let better = ghosts.best.get(&key).map_or(true, |best| {
+            (run.record.score, run.record.gates) > (best.record.score, best.record.gates)
+        });
+        if better {
+            ghosts.best.insert(key, run);
+        }
+
+        // シードはゲームごとに変わるので、溜まりすぎたら一番悪い走りから捨てる
+        if ghosts.best.len() > GHOST_MAX_RUNS {
+            if let Some(worst) = ghosts
+                .best
+                .iter()
+                .min_by_key(|(_, run)| (run.record.score, run.record.gates))
+                .map(|(key, _)| key.clone())
+            {
+                ghosts.best.remove(&worst);
+            }
+        }
+    }
+}
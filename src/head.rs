@@ -5,12 +5,10 @@ pub struct ModPlugin;
 impl Plugin for ModPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<ModResources>()
-            .add_startup_system(setup.system())
-            .add_system_to_stage(
-                stage::PRE_UPDATE,
-                select_movement_system.system().chain(void.system()),
-            )
-            .add_system(move_head_system.system().chain(void.system()))
+            .init_resource::<OrbitLimits>()
+            .add_system_to_stage(stage::PRE_UPDATE, select_movement_system.system())
+            .add_system(move_head_system.system())
+            .add_system_to_stage(stage::POST_UPDATE, orbit_ring_system.system())
             .add_system_to_stage(stage::LAST, on_game_start.system())
             .add_system_to_stage(stage::LAST, on_game_over.system());
     }
@@ -19,121 +17,292 @@ impl Plugin for ModPlugin {
 #[derive(Clone, PartialEq, Debug)]
 pub struct Head {}
 
-struct CenterMarker {}
+struct CenterMarker {
+    player: PlayerId,
+}
 
-struct ModResources {
-    mesh: Handle<Mesh>,
-    material: Handle<StandardMaterial>,
+// 回転の中心の周りに、回る円を点で描く
+struct OrbitRing {
+    player: PlayerId,
+    index: usize,
 }
 
-impl FromResources for ModResources {
-    fn from_resources(resources: &Resources) -> Self {
+// 回る半径の下限と上限
+pub struct OrbitLimits {
+    pub min_radius: f32,
+    pub max_radius: f32,
+}
+
+impl Default for OrbitLimits {
+    fn default() -> Self {
         Self {
-            mesh: resources
-                .get_mut::<Assets<Mesh>>()
-                .unwrap()
-                .add(Mesh::from(shape::Icosphere {
-                    radius: HEAD_SIZE,
-                    subdivisions: 5,
-                })),
-            material: resources
-                .get_mut::<Assets<StandardMaterial>>()
-                .unwrap()
-                .add(HEAD_COLOR.into()),
+            min_radius: ORBIT_MIN_RADIUS,
+            max_radius: ORBIT_MAX_RADIUS,
         }
     }
 }
 
-fn setup(
-    commands: &mut Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    commands
-        .spawn(PbrBundle {
+impl OrbitLimits {
+    // 半径がこのまま回り続けたときに落ち着く半径。下限と上限が逆でも止まらないように、上限を優先する
+    pub fn target(&self, radius: f32) -> f32 {
+        radius.max(self.min_radius).min(self.max_radius)
+    }
+}
+
+struct ModResources {
+    mesh: Handle<Mesh>,
+    marker_mesh: Handle<Mesh>,
+    ring_mesh: Handle<Mesh>,
+    // プレイヤーごとの色
+    materials: Vec<Handle<StandardMaterial>>,
+}
+
+impl ModResources {
+    fn material(&self, player: PlayerId) -> Handle<StandardMaterial> {
+        self.materials[player.0 % self.materials.len()].clone()
+    }
+}
+
+impl FromResources for ModResources {
+    fn from_resources(resources: &Resources) -> Self {
+        let mut meshes = resources.get_mut::<Assets<Mesh>>().unwrap();
+        let mut materials = resources.get_mut::<Assets<StandardMaterial>>().unwrap();
+        Self {
             mesh: meshes.add(Mesh::from(shape::Icosphere {
+                radius: HEAD_SIZE,
+                subdivisions: 5,
+            })),
+            marker_mesh: meshes.add(Mesh::from(shape::Icosphere {
                 radius: MARKER_SIZE,
                 subdivisions: 5,
             })),
-            material: materials.add(MARKER_COLOR.into()),
-            ..Default::default()
-        })
-        .with(CenterMarker {})
-        .with(Position::default(false));
+            ring_mesh: meshes.add(Mesh::from(shape::Icosphere {
+                radius: ORBIT_RING_DOT_SIZE,
+                subdivisions: 1,
+            })),
+            materials: PLAYER_COLORS
+                .iter()
+                .map(|color| materials.add((*color).into()))
+                .collect(),
+        }
+    }
+}
+
+// 生きている全員の頭の位置
+pub fn positions(
+    centipede_container: &CentipedeContainer,
+    head_query: &Query<&Position, With<Head>>,
+) -> Vec<Position> {
+    centipede_container
+        .head_entities()
+        .into_iter()
+        .flat_map(|entity| head_query.get(entity).ok().copied())
+        .collect()
 }
 
 fn select_movement_system(
     mut centipede_container: ResMut<CentipedeContainer>,
-    cursor_state: Res<input::CursorState>,
-    mut marker_query: Query<&mut Position, With<CenterMarker>>,
+    controls: Res<input::Controls>,
+    limits: Res<OrbitLimits>,
+    mut marker_query: Query<(&mut Position, &CenterMarker)>,
     head_query: Query<&Position, With<Head>>,
-) -> Option<()> {
-    let mut centipede = centipede_container.alive_mut()?;
-    let position = head_query.get(centipede.head_entity).ok()?;
-
-    let mut circular = false;
-    match centipede.movement {
-        Movement::Circular(_) => {
-            if !cursor_state.left_pressed {
-                centipede.movement = Movement::Linear(centipede.last_move);
+) {
+    for centipede in centipede_container.iter_alive_mut() {
+        let position = match head_query.get(centipede.head_entity) {
+            Ok(position) => position,
+            Err(_) => continue,
+        };
+        let control = controls.get(centipede.input);
+
+        let mut circular = false;
+        match centipede.movement.orbit() {
+            Some(CircularMove { center, clockwise }) => {
+                centipede.movement = if !control.pressed {
+                    Movement::Linear(centipede.last_move)
+                } else if control.spiral != 0.0 {
+                    // 押したまま締めたり緩めたりしている間は渦巻きになる
+                    Movement::Spiral(SpiralMove {
+                        center,
+                        clockwise,
+                        rate: -control.spiral.clamp(-1.0, 1.0) * SPIRAL_SPEED,
+                    })
+                } else {
+                    Movement::Circular(CircularMove { center, clockwise })
+                };
+                circular = true;
+            }
+            None => {
+                let vec = Vec2 {
+                    y: -control.position.x + position.x,
+                    x: control.position.y - position.y,
+                };
+                let inner_product = vec.x * centipede.last_move.x + vec.y * centipede.last_move.y;
+                if control.pressed {
+                    if let Some(movement) = orbit_around(
+                        position,
+                        &control.position,
+                        inner_product < 0.0,
+                        centipede.last_move,
+                        &limits,
+                    ) {
+                        centipede.movement = movement;
+                    }
+                }
             }
-            circular = true;
         }
-        Movement::Linear(_) => {
-            let vec = Vec2 {
-                y: -cursor_state.position.x + position.x,
-                x: cursor_state.position.y - position.y,
-            };
-            let inner_product = vec.x * centipede.last_move.x + vec.y * centipede.last_move.y;
-            if cursor_state.left_pressed {
-                centipede.movement = Movement::Circular(CircularMove {
-                    center: cursor_state.position.clone(),
-                    clockwise: inner_product < 0.0,
-                });
+
+        for (mut marker, _) in marker_query
+            .iter_mut()
+            .filter(|(_, marker)| marker.player == centipede.player)
+        {
+            // 回転してるときだけ表示
+            marker.visible = circular;
+
+            if !circular {
+                // 回転してない間は場所だけカーソルに追従する
+                marker.x = control.position.x;
+                marker.y = control.position.y;
             }
         }
     }
+}
 
-    for mut marker in marker_query.iter_mut() {
-        // 回転してるときだけ表示
-        marker.visible = circular;
+// centerの周りを回り始める。頭の上を押したなど中心が決まらないときは、進む向きの左側に下限の半径で回る
+// 進む向きも無ければ、まっすぐ進み続ける
+fn orbit_around(
+    position: &Position,
+    center: &Position,
+    clockwise: bool,
+    last_move: Vec2,
+    limits: &OrbitLimits,
+) -> Option<Movement> {
+    if !center.x.is_finite() || !center.y.is_finite() {
+        return None;
+    }
+    if position.distance(center) > GEOMETRY_EPSILON {
+        return Some(Movement::Circular(CircularMove {
+            center: *center,
+            clockwise,
+        }));
+    }
+    if last_move == (Vec2 { x: 0.0, y: 0.0 }) {
+        return None;
+    }
 
-        if !circular {
-            // 回転してない間は場所だけカーソルに追従する
-            marker.x = cursor_state.position.x;
-            marker.y = cursor_state.position.y;
+    let mut center = *position;
+    center.move_to_with_distance(
+        Vec2 {
+            x: -last_move.y,
+            y: last_move.x,
+        },
+        limits.target(limits.min_radius).max(GEOMETRY_EPSILON * 2.0),
+    );
+    Some(Movement::Circular(CircularMove {
+        center,
+        clockwise: false,
+    }))
+}
+
+// 回っている間、中心の周りに落ち着く先の円を出す
+fn orbit_ring_system(
+    centipede_container: Res<CentipedeContainer>,
+    limits: Res<OrbitLimits>,
+    head_query: Query<&Position, With<Head>>,
+    mut ring_query: Query<(&mut Position, &OrbitRing)>,
+) {
+    for centipede in centipede_container.iter_alive() {
+        let orbit = match (
+            centipede.movement.orbit(),
+            head_query.get(centipede.head_entity),
+        ) {
+            (Some(CircularMove { center, .. }), Ok(head)) => {
+                Some((center, limits.target(head.distance(&center))))
+            }
+            _ => None,
+        };
+
+        for (mut position, ring) in ring_query
+            .iter_mut()
+            .filter(|(_, ring)| ring.player == centipede.player)
+        {
+            match orbit {
+                Some((center, radius)) => {
+                    let radian =
+                        ring.index as f32 / ORBIT_RING_DOTS as f32 * std::f32::consts::PI * 2.0;
+                    position.x = center.x + radian.sin() * radius;
+                    position.y = center.y + radian.cos() * radius;
+                    position.visible = true;
+                }
+                None => position.visible = false,
+            }
         }
     }
-    None
 }
 
 fn move_head_system(
     mut centipede_container: ResMut<CentipedeContainer>,
-    time: Res<Time>,
+    time: Res<clock::Clock>,
+    board: Res<Board>,
+    obstacles: Res<obstacle::Obstacles>,
+    limits: Res<OrbitLimits>,
     mut head_query: Query<&mut Position, With<Head>>,
-) -> Option<()> {
-    let mut centipede = centipede_container.alive_mut()?;
-    let mut position = head_query.get_mut(centipede.head_entity).ok()?;
+) {
+    for centipede in centipede_container.iter_alive_mut() {
+        if let Ok(mut position) = head_query.get_mut(centipede.head_entity) {
+            move_head(centipede, &mut position, &time, &board, &obstacles, &limits);
+        }
+    }
+}
 
+fn move_head(
+    centipede: &mut Alive,
+    position: &mut Mut<Position>,
+    time: &clock::Clock,
+    board: &Board,
+    obstacles: &obstacle::Obstacles,
+    limits: &OrbitLimits,
+) {
     // 壁の外にいたら無条件に跳ね返す
-    reverse_head_move(&mut centipede, &mut position);
+    reverse_head_move(board, centipede, position);
+    bounce_head_move(centipede, position, obstacles);
 
     let distance = centipede.speed * time.delta_seconds();
-    let last_position = position.clone();
+    let last_position = **position;
 
-    match centipede.movement {
-        Movement::Circular(CircularMove { center, clockwise }) => {
+    match centipede.movement.orbit() {
+        Some(CircularMove { center, clockwise }) => {
             let radius: f32 = position.distance(&center);
-            let radian: f32 = (position.x - center.x).atan2(position.y - center.y)
-                + distance / radius * if clockwise { 1.0 } else { -1.0 };
+            if radius > GEOMETRY_EPSILON {
+                let radius = match centipede.movement {
+                    // 締めても緩めても、下限と上限の外へは出ない
+                    Movement::Spiral(SpiralMove { rate, .. }) => {
+                        let spiraled = radius + rate * time.delta_seconds();
+                        if rate < 0.0 {
+                            spiraled.max(radius.min(limits.min_radius))
+                        } else {
+                            spiraled.min(radius.max(limits.max_radius))
+                        }
+                    }
+                    _ => radius,
+                };
+                // 半径が下限と上限の外なら、いきなり変えずに渦を巻くように近づける
+                let ease = 1.0 - (-ORBIT_RADIUS_EASE * time.delta_seconds()).exp();
+                let radius = radius + (limits.target(radius) - radius) * ease;
+                let radian: f32 = (position.x - center.x).atan2(position.y - center.y)
+                    + distance / radius * if clockwise { 1.0 } else { -1.0 };
 
-            position.x = center.x + radian.sin() * radius;
-            position.y = center.y + radian.cos() * radius;
-            centipede.speed += time.delta_seconds() * SPEED_UP;
+                position.x = center.x + radian.sin() * radius;
+                position.y = center.y + radian.cos() * radius;
+            } else if centipede.last_move != (Vec2 { x: 0.0, y: 0.0 }) {
+                // 中心が頭と重なっていると回れないので、そのまままっすぐ進む
+                position.move_to_with_distance(centipede.last_move, distance);
+            }
         }
-        Movement::Linear(direction) => {
-            if direction != (Vec2 { x: 0.0, y: 0.0 }) {
-                position.move_to_with_distance(direction, distance);
+        None => {
+            if let Movement::Linear(direction) = centipede.movement {
+                if direction != (Vec2 { x: 0.0, y: 0.0 }) {
+                    position.move_to_with_distance(direction, distance);
+                }
             }
         }
     }
@@ -143,16 +312,16 @@ fn move_head_system(
         y: position.y - last_position.y,
     };
 
-    centipede.position_history.push(position.clone());
-    None
+    centipede.position_history.push(**position);
+    centipede.trim_history();
 }
 
-fn reverse_head_move(centipede: &mut Alive, position: &mut Mut<Position>) {
+fn reverse_head_move(board: &Board, centipede: &mut Alive, position: &mut Mut<Position>) {
     let (out_x, out_y) = (
-        position.x > constants::BOARD_X_BORDER && centipede.last_move.x > 0.0
-            || position.x < -constants::BOARD_X_BORDER && centipede.last_move.x < 0.0,
-        position.y > constants::BOARD_Y_BORDER && centipede.last_move.y > 0.0
-            || position.y < -constants::BOARD_Y_BORDER && centipede.last_move.y < 0.0,
+        position.x > board.x_border() && centipede.last_move.x > 0.0
+            || position.x < -board.x_border() && centipede.last_move.x < 0.0,
+        position.y > board.y_border() && centipede.last_move.y > 0.0
+            || position.y < -board.y_border() && centipede.last_move.y < 0.0,
     );
 
     if out_x || out_y {
@@ -164,25 +333,100 @@ fn reverse_head_move(centipede: &mut Alive, position: &mut Mut<Position>) {
     }
 }
 
+// 跳ね返る障害物に入り込んだら、入ってきた向きの成分を反転させる
+fn bounce_head_move(
+    centipede: &mut Alive,
+    position: &mut Mut<Position>,
+    obstacles: &obstacle::Obstacles,
+) {
+    if obstacles.get(position) != Some(obstacle::ObstacleKind::Wall) {
+        return;
+    }
+
+    let Vec2 { x, y } = centipede.last_move;
+    let (current, previous) = (
+        obstacles.board.panel(position),
+        obstacles.board.panel(&Position {
+            x: position.x - x,
+            y: position.y - y,
+            visible: true,
+        }),
+    );
+    let (cross_x, cross_y) = match (current, previous) {
+        (Some(current), Some(previous)) if current != previous => {
+            (current.x != previous.x, current.y != previous.y)
+        }
+        // 障害物の中から出られなくなっていたら来た道を戻る
+        _ => (true, true),
+    };
+
+    centipede.movement = Movement::Linear(Vec2 {
+        x: if cross_x { -x } else { x },
+        y: if cross_y { -y } else { y },
+    })
+}
+
+// 参加するプレイヤーを、出発地点から横に並べて出す
 fn on_game_start(
     commands: &mut Commands,
     resources: Res<ModResources>,
+    levels: Res<level::Levels>,
+    attract: Res<title::Attract>,
     mut centipede_container: ResMut<CentipedeContainer>,
     (events, mut reader): (Res<Events<GameStart>>, Local<EventReader<GameStart>>),
 ) {
     for _ in reader.iter(&events) {
-        centipede_container.centipede = Centipede::Alive(Alive::default(
-            commands
-                .spawn(PbrBundle {
-                    mesh: resources.mesh.clone(),
-                    material: resources.material.clone(),
-                    ..Default::default()
-                })
-                .with(Head {})
-                .with(Position::default(true))
-                .current_entity()
-                .unwrap(),
-        ));
+        let start = levels.start();
+        let direction = start.direction();
+        let players = attract.players(&centipede_container);
+        let count = players.len();
+
+        centipede_container.centipedes = players
+            .iter()
+            .enumerate()
+            .map(|(index, input)| {
+                let player = PlayerId(index);
+                let mut position = start.position();
+                position.move_to_with_distance(
+                    Vec2 {
+                        x: -direction.y,
+                        y: direction.x,
+                    },
+                    (index as f32 - (count - 1) as f32 / 2.0) * PLAYER_START_SPACING,
+                );
+
+                commands
+                    .spawn(PbrBundle {
+                        mesh: resources.marker_mesh.clone(),
+                        material: resources.material(player),
+                        ..Default::default()
+                    })
+                    .with(CenterMarker { player })
+                    .with(Position::default(false));
+                for index in 0..ORBIT_RING_DOTS {
+                    commands
+                        .spawn(PbrBundle {
+                            mesh: resources.ring_mesh.clone(),
+                            material: resources.material(player),
+                            ..Default::default()
+                        })
+                        .with(OrbitRing { player, index })
+                        .with(Position::default(false));
+                }
+
+                let head_entity = commands
+                    .spawn(PbrBundle {
+                        mesh: resources.mesh.clone(),
+                        material: resources.material(player),
+                        ..Default::default()
+                    })
+                    .with(Head {})
+                    .with(position)
+                    .current_entity()
+                    .unwrap();
+                Centipede::Alive(Alive::new(player, *input, head_entity, position, direction))
+            })
+            .collect();
     }
 }
 
@@ -190,8 +434,20 @@ fn on_game_over(
     commands: &mut Commands,
     events: Res<Events<GameOver>>,
     mut reader: Local<EventReader<GameOver>>,
+    marker_query: Query<(Entity, &CenterMarker)>,
+    ring_query: Query<(Entity, &OrbitRing)>,
 ) {
     for event in reader.iter(&events) {
         commands.despawn(event.head_entity);
+        for (entity, marker) in marker_query.iter() {
+            if marker.player == event.player {
+                commands.despawn(entity);
+            }
+        }
+        for (entity, ring) in ring_query.iter() {
+            if ring.player == event.player {
+                commands.despawn(entity);
+            }
+        }
     }
 }
@@ -1,4 +1,6 @@
 use crate::*;
+use bevy::input::mouse::MouseWheel;
+use std::collections::HashMap;
 
 pub struct ModPlugin;
 
@@ -6,7 +8,10 @@ pub struct ModPlugin;
 impl Plugin for ModPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<CursorState>()
-            .add_system_to_stage(stage::FIRST, read_input_events_system.system());
+            .init_resource::<Controls>()
+            .add_system_to_stage(stage::FIRST, read_input_events_system.system())
+            .add_system_to_stage(stage::FIRST, read_keyboard_system.system())
+            .add_system_to_stage(stage::PRE_UPDATE, join_system.system());
     }
 }
 
@@ -15,24 +20,157 @@ pub struct CursorState {
     pub screen_position: Vec2,
     pub position: Position,
     pub left_pressed: bool,
+    pub left_just_pressed: bool,
+    // 最後に回したホイールの向き(締めるなら正)と、それが続く時刻
+    pub wheel: f32,
+    pub wheel_until: f64,
+}
+
+// 頭の動かし方を決める入力元
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum InputSource {
+    // 左ボタンを押している間回る。右ボタンかホイールで締めたり緩めたりする
+    // 左Shiftでブレーキ、中ボタンでブースト
+    Mouse,
+    // WASDで回転の中心を動かし、Spaceを押している間回る。ZとXで締めたり緩めたりする
+    // Cでブレーキ、Vでブースト
+    Keyboard,
+    // autopilotが入力を作る。番号はbotごと
+    Bot(usize),
+}
+
+// 回転の中心と、押しているか。プレイヤーはどの入力元からもこれだけを受け取る
+#[derive(Copy, Clone, Default, PartialEq, Debug)]
+pub struct Control {
+    pub position: Position,
+    pub pressed: bool,
+    // 回っている間に半径を締める(正)か緩める(負)か。-1.0〜1.0
+    pub spiral: f32,
+    // 押している間減速する
+    pub brake: bool,
+    // 押した瞬間に加速する
+    pub boost: bool,
+}
+
+#[derive(Default)]
+pub struct Controls {
+    sources: HashMap<InputSource, Control>,
+}
+
+impl Controls {
+    pub fn get(&self, source: InputSource) -> Control {
+        self.sources.get(&source).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, source: InputSource, control: Control) {
+        self.sources.insert(source, control);
+    }
 }
 
 // bevyのResから、情報を読み取り、CursorStateを更新する
 fn read_input_events_system(
+    time: Res<clock::Clock>,
     mouse_input: Res<Input<MouseButton>>,
+    keyboard_input: Res<Input<KeyCode>>,
     windows: Res<Windows>,
     mut cursor_state: ResMut<CursorState>,
+    mut controls: ResMut<Controls>,
     (events, mut reader): (Res<Events<CursorMoved>>, Local<EventReader<CursorMoved>>),
+    (wheel_events, mut wheel_reader): (Res<Events<MouseWheel>>, Local<EventReader<MouseWheel>>),
 ) {
     // cursorは左下が0, 0、Vec2は真ん中が0, 0
     for event in reader.iter(&events) {
         cursor_state.screen_position = event.position;
     }
 
+    // ホイールは一目盛りごとに、少しの間だけ締めたり緩めたりし続ける
+    for event in wheel_reader.iter(&wheel_events) {
+        if event.y != 0.0 {
+            cursor_state.wheel = event.y.signum();
+            cursor_state.wheel_until = time.seconds_since_startup() + SPIRAL_WHEEL_SECONDS;
+        }
+    }
+    let wheel = if time.seconds_since_startup() < cursor_state.wheel_until {
+        cursor_state.wheel
+    } else {
+        0.0
+    };
+
     // マウスの左ボタン状態
     cursor_state.left_pressed = mouse_input.pressed(MouseButton::Left);
+    cursor_state.left_just_pressed = mouse_input.just_pressed(MouseButton::Left);
 
-    let window = windows.get_primary().unwrap();
+    // 窓の無いとき(soakなど)はマウスを読まない
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
     cursor_state.position.x = cursor_state.screen_position.x - window.width() / 2.0;
     cursor_state.position.y = cursor_state.screen_position.y - window.height() / 2.0;
+
+    controls.set(
+        InputSource::Mouse,
+        Control {
+            position: cursor_state.position,
+            pressed: cursor_state.left_pressed,
+            spiral: if mouse_input.pressed(MouseButton::Right) {
+                1.0
+            } else {
+                wheel
+            },
+            brake: keyboard_input.pressed(MOUSE_BRAKE_KEY),
+            boost: mouse_input.pressed(MouseButton::Middle),
+        },
+    );
+}
+
+// キーボードで動かす見えないカーソル。盤面の外には出ない
+fn read_keyboard_system(
+    time: Res<clock::Clock>,
+    keyboard_input: Res<Input<KeyCode>>,
+    board: Res<Board>,
+    mut controls: ResMut<Controls>,
+) {
+    let mut control = controls.get(InputSource::Keyboard);
+    let axis = |minus: KeyCode, plus: KeyCode| {
+        keyboard_input.pressed(plus) as i32 as f32 - keyboard_input.pressed(minus) as i32 as f32
+    };
+    let distance = KEYBOARD_CURSOR_SPEED * time.delta_seconds();
+
+    control.position.x = (control.position.x
+        + axis(KEYBOARD_LEFT_KEY, KEYBOARD_RIGHT_KEY) * distance)
+        .clamp(-board.x_border(), board.x_border());
+    control.position.y = (control.position.y + axis(KEYBOARD_DOWN_KEY, KEYBOARD_UP_KEY) * distance)
+        .clamp(-board.y_border(), board.y_border());
+    control.pressed = keyboard_input.pressed(KEYBOARD_HOLD_KEY);
+    control.spiral = axis(KEYBOARD_LOOSEN_KEY, KEYBOARD_TIGHTEN_KEY);
+    control.brake = keyboard_input.pressed(KEYBOARD_BRAKE_KEY);
+    control.boost = keyboard_input.pressed(KEYBOARD_BOOST_KEY);
+
+    controls.set(InputSource::Keyboard, control);
+}
+
+// Jでキーボードの二人目を加えたり外したりして、最初からやり直す
+fn join_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    editor: Res<editor::Editor>,
+    session: Res<mode::Session>,
+    mut centipede_container: ResMut<CentipedeContainer>,
+    mut game_over_events: ResMut<Events<GameOver>>,
+) {
+    if editor.active || !keyboard_input.just_pressed(JOIN_KEY) {
+        return;
+    }
+
+    let players = &mut centipede_container.players;
+    if players.contains(&InputSource::Keyboard) {
+        // 遊び方に必要な人数は下回らない
+        if players.len() <= session.mode.min_players() {
+            return;
+        }
+        players.retain(|source| *source != InputSource::Keyboard);
+    } else {
+        players.push(InputSource::Keyboard);
+    }
+    centipede_container.finish(0.0, GameOverCause::Aborted, &mut game_over_events);
 }
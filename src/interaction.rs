@@ -4,85 +4,403 @@ pub struct ModPlugin;
 
 impl Plugin for ModPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system_to_stage(
-            stage::SEND_EVENT,
-            head_and_gate_system.system().chain(void.system()),
-        )
-        .add_system_to_stage(
-            stage::SEND_EVENT,
-            head_and_tail_system.system().chain(void.system()),
-        );
+        app.init_resource::<SelfCollision>()
+            .add_system_to_stage(stage::SEND_EVENT, head_and_gate_system.system())
+            .add_system_to_stage(stage::SEND_EVENT, head_and_tail_system.system())
+            .add_system_to_stage(stage::SEND_EVENT, head_and_head_system.system())
+            .add_system_to_stage(stage::SEND_EVENT, head_and_enemy_system.system())
+            .add_system_to_stage(stage::SEND_EVENT, enemy_and_tail_system.system())
+            .add_system_to_stage(stage::SEND_EVENT, head_and_obstacle_system.system());
     }
 }
 
+// 画面に出ているものの位置。隠れているもの(奥に置いたもの)とはぶつからない
+fn visible(transform: &GlobalTransform) -> Option<Position> {
+    Some(Position::from(transform.translation)).filter(|position| position.visible)
+}
+
+// 頭が門に当たったときに送るもの
+enum GateHit {
+    Crush(CrushPoll),
+    Through(ThroughGate),
+}
+
+// 速くなると一刻みで柱や尾を飛び越えてしまうので、頭がこの刻みに動いた線分で調べる
 fn head_and_gate_system(
     commands: &mut Commands,
     mut through_gate_events: ResMut<Events<ThroughGate>>,
     mut crush_gate_events: ResMut<Events<CrushPoll>>,
     centipede_container: Res<CentipedeContainer>,
+    broadphase: Res<broadphase::Broadphase>,
     head_query: Query<&GlobalTransform, With<head::Head>>,
-    gate_query: Query<(Entity, &Children), With<gate::Gate>>,
+    gate_query: Query<&Children, With<gate::Gate>>,
     poll_query: Query<&GlobalTransform, With<gate::Poll>>,
-) -> Option<()> {
-    let centipede = centipede_container.alive()?;
-    let head_translation = head_query.get(centipede.head_entity).ok()?.translation;
-
-    for (gate, children) in gate_query.iter() {
-        let poll_translations: Vec<Vec3> = children
-            .iter()
-            .flat_map(|poll_entity| poll_query.get(*poll_entity))
-            .map(|poll_transform| poll_transform.translation)
-            .collect();
-
-        // Gateの両脇にあたったらミス
-        for poll_translation in &poll_translations {
-            if head_translation.distance(poll_translation.clone())
-                <= constants::POLL_SIZE + constants::HEAD_SIZE
-            {
-                // ここで消さないと次のフレームで再度衝突する
-                commands.despawn_recursive(gate);
-                // このイベント使ってないが
-                crush_gate_events.send(CrushPoll {});
-            }
+) {
+    // 門ごとに、この刻みのどこで当たったか
+    let mut hits: Vec<(f32, Entity, GateHit)> = vec![];
+
+    for centipede in centipede_container.iter_alive() {
+        if head_query
+            .get(centipede.head_entity)
+            .ok()
+            .and_then(visible)
+            .is_none()
+        {
+            continue;
         }
+        let (from, to) = centipede.last_step();
+
+        let reach = constants::POLL_SIZE + constants::HEAD_SIZE;
+        for gate in broadphase.gates.query(&from, &to, reach) {
+            let children = match gate_query.get(gate) {
+                Ok(children) => children,
+                _ => continue,
+            };
+            let poll_positions: Vec<Position> = children
+                .iter()
+                .flat_map(|poll_entity| poll_query.get(*poll_entity))
+                .flat_map(visible)
+                .collect();
+
+            // Gateの両脇にあたったらミス
+            let crush = poll_positions
+                .iter()
+                .flat_map(|poll_position| geometry::sweep(&from, &to, poll_position, reach))
+                .fold(None, |earliest: Option<f32>, ratio| {
+                    Some(earliest.map_or(ratio, |earliest| earliest.min(ratio)))
+                });
 
-        // 門の中をくぐったら、OK
-        if let (Some(head1), Some(head2), Some(poll1), Some(poll2)) = (
-            centipede.position_history.last(),
-            centipede
-                .position_history
-                .get(centipede.position_history.len() - 2),
-            poll_translations.get(0),
-            poll_translations.get(1),
-        ) {
-            if intersection(head1, head2, &(*poll1).into(), &(*poll2).into()) {
-                commands.despawn_recursive(gate);
-                through_gate_events.send(ThroughGate {});
+            // 門の中をくぐったら、OK
+            let polls = match poll_positions.as_slice() {
+                [poll1, poll2, ..] => Some((poll1, poll2)),
+                _ => None,
+            };
+            let through =
+                polls.and_then(|(poll1, poll2)| geometry::crossing(&from, &to, poll1, poll2));
+            let facts = polls.map(|(poll1, poll2)| GateFacts::new(gate, poll1, poll2));
+
+            // 同じ刻みで両方起きたら、先に起きた方。同時なら柱
+            match (crush, through, facts) {
+                (Some(crush), through, _) if through.map_or(true, |through| crush <= through) => {
+                    hits.push((
+                        crush,
+                        gate,
+                        GateHit::Crush(CrushPoll {
+                            player: centipede.player,
+                            cause: CrushCause::Poll,
+                            gate: facts,
+                            position: geometry::point_along(&from, &to, crush),
+                            head: HeadFacts::new(
+                                centipede,
+                                tail::tails_after_crush(centipede.tail_count),
+                            ),
+                        }),
+                    ));
+                }
+                (_, Some(through), Some(facts)) => {
+                    hits.push((
+                        through,
+                        gate,
+                        GateHit::Through(ThroughGate {
+                            player: centipede.player,
+                            gate: facts,
+                            position: geometry::point_along(&from, &to, through),
+                            head: HeadFacts::new(centipede, centipede.tail_count + 1),
+                        }),
+                    ));
+                }
+                _ => {}
             }
         }
     }
-    None
+
+    // 同じ門に二匹が同じ刻みで当たったら、刻みの中で先に当たった方のもの
+    hits.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut consumed = vec![];
+    for (_, gate, hit) in hits {
+        if consumed.contains(&gate) {
+            continue;
+        }
+        // ここで消さないと次のフレームで再度衝突する
+        commands.despawn_recursive(gate);
+        consumed.push(gate);
+        match hit {
+            GateHit::Crush(event) => crush_gate_events.send(event),
+            GateHit::Through(event) => through_gate_events.send(event),
+        }
+    }
 }
 
+// 自分の尾との当たり判定の決まり。遊び方や試験で変えられる
+// 小さく回って頭がすぐ後ろの尾に重ならないようにするのは、回る半径の下限(OrbitLimits)の役目
+pub struct SelfCollision {
+    // 頭のすぐ後ろのこの数の尾には、自分では当たらない
+    pub grace_tails: usize,
+}
+
+impl Default for SelfCollision {
+    fn default() -> Self {
+        Self {
+            grace_tails: SELF_COLLISION_GRACE_TAILS,
+        }
+    }
+}
+
+impl SelfCollision {
+    // 頭が、自分のtail_index番目の尾に当たらないか
+    pub fn ignores(&self, tail_index: usize) -> bool {
+        tail_index < self.grace_tails
+    }
+}
+
+// 頭が誰かの尾にぶつかったら、その尾の持ち主の尾を切る
+// 尾も同じ刻みに動くので、前の刻みの位置(GlobalTransform)から今の位置まで、互いに動いた分で調べる
+// 重なっている間ずっと切らないように、触れた瞬間だけ。一度に何本も触れたら一番頭に近い尾
 fn head_and_tail_system(
     mut eat_tail_events: ResMut<Events<EatTail>>,
     centipede_container: Res<CentipedeContainer>,
+    self_collision: Res<SelfCollision>,
+    broadphase: Res<broadphase::Broadphase>,
     head_query: Query<&GlobalTransform, With<head::Head>>,
-    tail_query: Query<(&tail::LivingTail, &GlobalTransform)>,
-) -> Option<()> {
-    let head_translation = head_query
-        .get(centipede_container.head_entity()?)
-        .ok()?
-        .translation;
+    tail_query: Query<(&tail::LivingTail, &GlobalTransform, &Position)>,
+    mut touching: Local<Vec<(PlayerId, Entity)>>,
+) {
+    let mut now_touching = vec![];
+    let mut bites: Vec<EatTail> = vec![];
 
-    for (tail, tail_global_transform) in tail_query.iter() {
-        let tail_translation = tail_global_transform.translation;
+    for centipede in centipede_container.iter_alive() {
+        if head_query
+            .get(centipede.head_entity)
+            .ok()
+            .and_then(visible)
+            .is_none()
+        {
+            continue;
+        }
+        let (from, to) = centipede.last_step();
 
-        if head_translation.distance(tail_translation.clone()) <= constants::HEAD_SIZE {
+        for entity in broadphase.tails.query(&from, &to, constants::HEAD_SIZE) {
+            let (tail, tail_from, tail_position) = match tail_query.get(entity) {
+                Ok((tail, tail_global_transform, tail_position)) => {
+                    match visible(tail_global_transform) {
+                        Some(tail_from) if tail_position.visible => {
+                            (tail, tail_from, tail_position)
+                        }
+                        _ => continue,
+                    }
+                }
+                Err(_) => continue,
+            };
+            if tail.player == centipede.player && self_collision.ignores(tail.index) {
+                continue;
+            }
+            if geometry::sweep_pair(&from, &to, &tail_from, tail_position, constants::HEAD_SIZE)
+                .is_none()
+            {
+                continue;
+            }
+
+            let contact = (centipede.player, entity);
+            now_touching.push(contact);
+            if touching.contains(&contact) {
+                continue;
+            }
+            match bites
+                .iter_mut()
+                .find(|bite| bite.eater == Some(centipede.player) && bite.player == tail.player)
+            {
+                Some(bite) => {
+                    if tail.index < bite.tail_index {
+                        bite.tail_index = tail.index;
+                        bite.position = *tail_position;
+                    }
+                }
+                None => bites.push(EatTail {
+                    player: tail.player,
+                    tail_index: tail.index,
+                    eater: Some(centipede.player),
+                    position: *tail_position,
+                    tails_before: centipede_container
+                        .alive(tail.player)
+                        .map_or(0, |owner| owner.tail_count),
+                }),
+            }
+        }
+    }
+
+    for bite in bites {
+        eat_tail_events.send(bite);
+    }
+    *touching = now_touching;
+}
+
+// 対戦では頭同士がぶつかったら、両方とも柱に激突したのと同じ扱い
+// 触れている間ずっと減らないように、触れた瞬間だけ
+fn head_and_head_system(
+    session: Res<mode::Session>,
+    mut crush_poll_events: ResMut<Events<CrushPoll>>,
+    centipede_container: Res<CentipedeContainer>,
+    broadphase: Res<broadphase::Broadphase>,
+    head_query: Query<&Position, With<head::Head>>,
+    mut touching: Local<Vec<(PlayerId, PlayerId)>>,
+) {
+    if !session.mode.heads_collide() {
+        touching.clear();
+        return;
+    }
+
+    let heads: Vec<_> = centipede_container
+        .iter_alive()
+        .flat_map(|centipede| {
+            head_query
+                .get(centipede.head_entity)
+                .ok()
+                .map(|position| (centipede.head_entity, centipede.player, *position))
+        })
+        .collect();
+
+    let mut now_touching = vec![];
+    for (_, player1, position1) in heads.iter() {
+        let reach = constants::HEAD_SIZE * 2.0;
+        for entity in broadphase.heads.query(position1, position1, reach) {
+            let (player2, position2) = match heads.iter().find(|(head, _, _)| *head == entity) {
+                Some((_, player2, position2)) if player2.0 > player1.0 => (player2, position2),
+                _ => continue,
+            };
+            if !geometry::touching(position1, position2, reach) {
+                continue;
+            }
+            let pair = (*player1, *player2);
+            if !touching.contains(&pair) {
+                for &(player, position) in [(*player1, *position1), (*player2, *position2)].iter() {
+                    if let Some(centipede) = centipede_container.alive(player) {
+                        crush_poll_events.send(CrushPoll {
+                            player,
+                            cause: CrushCause::Head,
+                            gate: None,
+                            position,
+                            head: HeadFacts::new(
+                                centipede,
+                                tail::tails_after_crush(centipede.tail_count),
+                            ),
+                        });
+                    }
+                }
+            }
+            now_touching.push(pair);
+        }
+    }
+    *touching = now_touching;
+}
+
+// 敵が頭にぶつかったら、柱に激突したのと同じ扱い
+fn head_and_enemy_system(
+    commands: &mut Commands,
+    mut crush_poll_events: ResMut<Events<CrushPoll>>,
+    centipede_container: Res<CentipedeContainer>,
+    broadphase: Res<broadphase::Broadphase>,
+    head_query: Query<&Position, With<head::Head>>,
+    enemy_query: Query<&Position, With<enemy::Enemy>>,
+) {
+    let mut consumed = vec![];
+
+    for centipede in centipede_container.iter_alive() {
+        let head_position = match head_query.get(centipede.head_entity) {
+            Ok(position) => position,
+            Err(_) => continue,
+        };
+
+        let reach = constants::ENEMY_SIZE + constants::HEAD_SIZE;
+        for enemy in broadphase
+            .enemies
+            .query(head_position, head_position, reach)
+        {
+            let enemy_position = match enemy_query.get(enemy) {
+                Ok(position) if !consumed.contains(&enemy) => position,
+                _ => continue,
+            };
+            if geometry::touching(head_position, enemy_position, reach) {
+                commands.despawn_recursive(enemy);
+                consumed.push(enemy);
+                crush_poll_events.send(CrushPoll {
+                    player: centipede.player,
+                    cause: CrushCause::Enemy,
+                    gate: None,
+                    position: *head_position,
+                    head: HeadFacts::new(centipede, tail::tails_after_crush(centipede.tail_count)),
+                });
+            }
+        }
+    }
+}
+
+// 敵が尾にぶつかったら、尾を食べたのと同じくそこから先を切り離す
+// 同じ刻みに二体が同じ尾に触れても、切るのは一度だけ
+fn enemy_and_tail_system(
+    commands: &mut Commands,
+    mut eat_tail_events: ResMut<Events<EatTail>>,
+    centipede_container: Res<CentipedeContainer>,
+    broadphase: Res<broadphase::Broadphase>,
+    tail_query: Query<(&tail::LivingTail, &Position)>,
+    enemy_query: Query<(Entity, &Position), With<enemy::Enemy>>,
+) {
+    let reach = constants::ENEMY_SIZE + constants::TAIL_SIZE / 2.0;
+    let mut eaten = vec![];
+    for (enemy, enemy_position) in enemy_query.iter() {
+        let hit = broadphase
+            .tails
+            .query(enemy_position, enemy_position, reach)
+            .into_iter()
+            .flat_map(|entity| tail_query.get(entity))
+            .filter(|(_, tail_position)| tail_position.visible)
+            .filter(|(_, tail_position)| geometry::touching(enemy_position, tail_position, reach))
+            .map(|(tail, tail_position)| (tail.index, tail.player, *tail_position))
+            .min_by_key(|(index, _, _)| *index);
+
+        if let Some((tail_index, player, position)) = hit {
+            commands.despawn_recursive(enemy);
+            if eaten.contains(&(player, tail_index)) {
+                continue;
+            }
+            eaten.push((player, tail_index));
             eat_tail_events.send(EatTail {
-                tail_index: tail.index,
+                player,
+                tail_index,
+                eater: None,
+                position,
+                tails_before: centipede_container
+                    .alive(player)
+                    .map_or(0, |owner| owner.tail_count),
             });
         }
     }
-    None
+}
+
+// 小惑星に激突したら柱と同じ扱い。小惑星は壊れる
+fn head_and_obstacle_system(
+    commands: &mut Commands,
+    mut crush_poll_events: ResMut<Events<CrushPoll>>,
+    mut obstacles: ResMut<obstacle::Obstacles>,
+    centipede_container: Res<CentipedeContainer>,
+    head_query: Query<&Position, With<head::Head>>,
+) {
+    for centipede in centipede_container.iter_alive() {
+        let head_position = match head_query.get(centipede.head_entity) {
+            Ok(position) => position,
+            Err(_) => continue,
+        };
+
+        if obstacles.get(head_position) == Some(obstacle::ObstacleKind::Asteroid) {
+            if let Some(entity) = obstacles.remove(head_position) {
+                commands.despawn_recursive(entity);
+                crush_poll_events.send(CrushPoll {
+                    player: centipede.player,
+                    cause: CrushCause::Asteroid,
+                    gate: None,
+                    position: *head_position,
+                    head: HeadFacts::new(centipede, tail::tails_after_crush(centipede.tail_count)),
+                });
+            }
+        }
+    }
 }
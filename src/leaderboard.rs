@@ -0,0 +1,118 @@
+use crate::*;
+use std::collections::HashMap;
+
+pub struct ModPlugin;
+
+// 遊び方とステージごとの上位の記録
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Leaderboard>()
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_game_start.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_game_over.system());
+    }
+}
+
+// 記録を分ける単位
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BoardKey {
+    pub mode: mode::GameMode,
+    pub level: Option<String>,
+}
+
+impl BoardKey {
+    pub fn current(session: &mode::Session, levels: &level::Levels) -> Self {
+        Self {
+            mode: session.mode,
+            level: levels.current().map(|level| level.name.clone()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Default, PartialEq, Debug)]
+pub struct Record {
+    pub score: usize,
+    pub gates: usize,
+}
+
+// 直前のゲームの記録と順位(1始まり、圏外ならNone)
+#[derive(Copy, Clone, Debug)]
+pub struct LastRun {
+    pub player: PlayerId,
+    pub record: Record,
+    pub rank: Option<usize>,
+    pub cause: GameOverCause,
+}
+
+#[derive(Default)]
+pub struct Leaderboard {
+    boards: HashMap<BoardKey, Vec<Record>>,
+    pub last: Option<LastRun>,
+}
+
+impl Leaderboard {
+    pub fn records(&self, key: &BoardKey) -> &[Record] {
+        self.boards
+            .get(key)
+            .map_or(&[], |records| records.as_slice())
+    }
+
+    pub fn best(&self, key: &BoardKey) -> Option<&Record> {
+        self.records(key).first()
+    }
+
+    // スコア、くぐった門の数の順に並べ、順位を返す
+    fn add(&mut self, key: BoardKey, record: Record) -> Option<usize> {
+        let records = self.boards.entry(key).or_default();
+        let rank = records
+            .iter()
+            .position(|other| (record.score, record.gates) > (other.score, other.gates))
+            .unwrap_or(records.len());
+        records.insert(rank, record);
+        records.truncate(LEADERBOARD_SIZE);
+
+        if rank < LEADERBOARD_SIZE {
+            Some(rank + 1)
+        } else {
+            None
+        }
+    }
+}
+
+fn on_game_start(
+    mut leaderboard: ResMut<Leaderboard>,
+    (events, mut reader): (Res<Events<GameStart>>, Local<EventReader<GameStart>>),
+) {
+    for _ in reader.iter(&events) {
+        leaderboard.last = None;
+    }
+}
+
+// 打ち切ったゲームと、記録を残さない遊び方、タイトル画面のデモは記録しない
+fn on_game_over(
+    session: Res<mode::Session>,
+    attract: Res<title::Attract>,
+    levels: Res<level::Levels>,
+    status: Res<ui::Status>,
+    mut leaderboard: ResMut<Leaderboard>,
+    (events, mut reader): (Res<Events<GameOver>>, Local<EventReader<GameOver>>),
+) {
+    for event in reader.iter(&events) {
+        if event.cause == GameOverCause::Aborted || !session.mode.records_score() || attract.active
+        {
+            continue;
+        }
+
+        let player_status = status.get(event.player);
+        let record = Record {
+            score: player_status.score,
+            gates: player_status.gates,
+        };
+        let rank = leaderboard.add(BoardKey::current(&session, &levels), record);
+        leaderboard.last = Some(LastRun {
+            player: event.player,
+            record,
+            rank,
+            cause: event.cause,
+        });
+    }
+}
@@ -0,0 +1,333 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+pub struct ModPlugin;
+
+// ステージの読み込みと選択、クリア判定
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Levels>()
+            .init_resource::<LevelProgress>()
+            .add_system_to_stage(
+                stage::PRE_UPDATE,
+                select_level_system.system().chain(void.system()),
+            )
+            .add_system_to_stage(
+                stage::POST_UPDATE,
+                level_goal_system.system().chain(void.system()),
+            )
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_game_start.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_through_gate.system());
+    }
+}
+
+// ステージファイル(RON)の中身
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Level {
+    pub name: String,
+    // 読み込んだファイル。エディタで保存するときに使う
+    #[serde(skip)]
+    pub file: Option<PathBuf>,
+    #[serde(default)]
+    pub board: Board,
+    #[serde(default)]
+    pub start: Start,
+    #[serde(default)]
+    pub obstacles: Vec<ObstacleSpec>,
+    // 最初から置かれている門
+    #[serde(default)]
+    pub gates: Vec<GateSpec>,
+    // 開始からの秒数で出す門
+    #[serde(default)]
+    pub spawns: Vec<ScriptedSpawn>,
+    // ランダムな門も出すか。出し方を指定しなければ標準のもの
+    #[serde(default)]
+    pub random_gates: bool,
+    #[serde(default)]
+    pub director: Option<director::Director>,
+    #[serde(default)]
+    pub goal: Goal,
+    #[serde(default)]
+    pub par_score: usize,
+}
+
+impl Level {
+    // エディタで新しく作るステージ
+    pub fn new(name: String, board: Board) -> Self {
+        Self {
+            name,
+            file: None,
+            board,
+            start: Start::default(),
+            obstacles: vec![],
+            gates: vec![],
+            spawns: vec![],
+            random_gates: false,
+            director: None,
+            goal: Goal::default(),
+            par_score: 0,
+        }
+    }
+}
+
+// 頭の出発地点と向き(度、0で右向き)
+#[derive(Copy, Clone, Default, Debug, Serialize, Deserialize)]
+pub struct Start {
+    pub x: f32,
+    pub y: f32,
+    pub direction: f32,
+}
+
+impl Start {
+    pub fn position(&self) -> Position {
+        Position {
+            x: self.x,
+            y: self.y,
+            visible: true,
+        }
+    }
+
+    pub fn direction(&self) -> Vec2 {
+        let (sin, cos) = self.direction.to_radians().sin_cos();
+        Vec2 { x: cos, y: sin }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct ObstacleSpec {
+    pub panel: Panel,
+    pub kind: obstacle::ObstacleKind,
+}
+
+// 門の中心、幅、回転(度)
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct GateSpec {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub rotation: f32,
+}
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct ScriptedSpawn {
+    pub at: f64,
+    pub gate: GateSpec,
+}
+
+// 指定された条件を全部満たしたらクリア。何も指定がなければ終わりなし
+#[derive(Copy, Clone, Default, Debug, Serialize, Deserialize)]
+pub struct Goal {
+    #[serde(default)]
+    pub gates: Option<usize>,
+    #[serde(default)]
+    pub score: Option<usize>,
+}
+
+impl Goal {
+    pub fn reached(&self, gates: usize, score: usize) -> bool {
+        (self.gates.is_some() || self.score.is_some())
+            && self.gates.map_or(true, |goal| gates >= goal)
+            && self.score.map_or(true, |goal| score >= goal)
+    }
+}
+
+// 読み込んだステージと、選択中のステージ。Noneならエンドレス
+pub struct Levels {
+    pub list: Vec<Level>,
+    pub selected: Option<usize>,
+}
+
+impl Levels {
+    pub fn current(&self) -> Option<&Level> {
+        self.list.get(self.selected?)
+    }
+
+    pub fn current_mut(&mut self) -> Option<&mut Level> {
+        self.list.get_mut(self.selected?)
+    }
+
+    // 選択中のステージの出発地点。エンドレスなら真ん中から右向き
+    pub fn start(&self) -> Start {
+        self.current()
+            .map_or_else(Start::default, |level| level.start)
+    }
+}
+
+impl FromResources for Levels {
+    fn from_resources(_resources: &Resources) -> Self {
+        Self {
+            list: load_levels(),
+            selected: None,
+        }
+    }
+}
+
+// 今回のゲームでくぐった門の数と、クリアしたか
+#[derive(Default)]
+pub struct LevelProgress {
+    pub gates: usize,
+    pub cleared: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn level_directory() -> PathBuf {
+    bevy::asset::FileAssetIo::get_root_path()
+        .join(ASSET_DIRECTORY)
+        .join(LEVEL_DIRECTORY)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_levels() -> Vec<Level> {
+    let directory = level_directory();
+
+    let mut paths: Vec<_> = match std::fs::read_dir(&directory) {
+        Ok(entries) => entries
+            .flat_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().and_then(|ext| ext.to_str()) == Some(LEVEL_EXTENSION))
+            .collect(),
+        Err(e) => {
+            warn!("failed to read {:?}: {}", directory, e);
+            vec![]
+        }
+    };
+    paths.sort();
+
+    paths
+        .iter()
+        .flat_map(|path| match std::fs::read_to_string(path) {
+            Ok(source) => {
+                parse_level(&source, path.to_string_lossy().as_ref()).map(|level| Level {
+                    file: Some(path.clone()),
+                    ..level
+                })
+            }
+            Err(e) => {
+                warn!("failed to read {:?}: {}", path, e);
+                None
+            }
+        })
+        .collect()
+}
+
+// webではファイルを読めないので埋め込む
+#[cfg(target_arch = "wasm32")]
+fn load_levels() -> Vec<Level> {
+    [
+        (
+            "01_first_steps",
+            include_str!("../assets/levels/01_first_steps.ron"),
+        ),
+        (
+            "02_asteroid_belt",
+            include_str!("../assets/levels/02_asteroid_belt.ron"),
+        ),
+        ("03_rush", include_str!("../assets/levels/03_rush.ron")),
+    ]
+    .iter()
+    .flat_map(|(name, source)| parse_level(source, name))
+    .collect()
+}
+
+// 読めないステージや遊べないステージは警告を出して飛ばす
+pub fn parse_level(source: &str, name: &str) -> Option<Level> {
+    match ron::de::from_str::<Level>(source)
+        .map_err(|e| e.to_string())
+        .and_then(check_level)
+    {
+        Ok(level) => Some(level),
+        Err(e) => {
+            warn!("failed to parse level {}: {}", name, e);
+            None
+        }
+    }
+}
+
+// 盤面が小さすぎたり、門を出す時刻が数でなかったりしたら読まない
+fn check_level(mut level: Level) -> Result<Level, String> {
+    if level.board.x < MIN_BOARD_PANELS || level.board.y < MIN_BOARD_PANELS {
+        return Err(format!(
+            "board {}x{} is smaller than {}x{}",
+            level.board.x, level.board.y, MIN_BOARD_PANELS, MIN_BOARD_PANELS
+        ));
+    }
+    if let Some(spawn) = level.spawns.iter().find(|spawn| !spawn.at.is_finite()) {
+        return Err(format!("spawn time {} is not a finite number", spawn.at));
+    }
+    level.spawns.sort_by(|a, b| a.at.total_cmp(&b.at));
+    Ok(level)
+}
+
+// 0でエンドレス、1〜9でステージを選び、最初からやり直す
+fn select_level_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    editor: Res<editor::Editor>,
+    mut levels: ResMut<Levels>,
+    mut board: ResMut<Board>,
+    mut centipede_container: ResMut<CentipedeContainer>,
+    mut game_over_events: ResMut<Events<GameOver>>,
+) -> Option<()> {
+    if editor.active {
+        return None;
+    }
+
+    let selected = LEVEL_KEYS
+        .iter()
+        .position(|key| keyboard_input.just_pressed(*key))?;
+
+    levels.selected = match selected {
+        0 => None,
+        n if n <= levels.list.len() => Some(n - 1),
+        _ => return None,
+    };
+    *board = levels
+        .current()
+        .map_or_else(Board::default, |level| level.board);
+
+    centipede_container.finish(0.0, GameOverCause::Aborted, &mut game_over_events);
+    None
+}
+
+// 目標を達成したらクリアとしてゲームを終える
+fn level_goal_system(
+    time: Res<clock::Clock>,
+    levels: Res<Levels>,
+    status: Res<ui::Status>,
+    mut progress: ResMut<LevelProgress>,
+    mut centipede_container: ResMut<CentipedeContainer>,
+    mut game_over_events: ResMut<Events<GameOver>>,
+) -> Option<()> {
+    let level = levels.current()?;
+    if !centipede_container.playing() {
+        return None;
+    }
+
+    if level.goal.reached(progress.gates, status.best().score) {
+        progress.cleared = true;
+        centipede_container.finish(
+            time.seconds_since_startup(),
+            GameOverCause::Cleared,
+            &mut game_over_events,
+        );
+    }
+    None
+}
+
+fn on_game_start(
+    mut progress: ResMut<LevelProgress>,
+    (events, mut reader): (Res<Events<GameStart>>, Local<EventReader<GameStart>>),
+) {
+    for _ in reader.iter(&events) {
+        *progress = LevelProgress::default();
+    }
+}
+
+fn on_through_gate(
+    mut progress: ResMut<LevelProgress>,
+    (events, mut reader): (Res<Events<ThroughGate>>, Local<EventReader<ThroughGate>>),
+) {
+    for _ in reader.iter(&events) {
+        progress.gates += 1;
+    }
+}
@@ -0,0 +1,71 @@
+// bevyのsystemは引数が多くなりがちなので
+#![allow(clippy::too_many_arguments)]
+
+pub use bevy::prelude::*;
+
+use crate::event::*;
+use bevy::app::PluginGroupBuilder;
+pub use constants::*;
+pub use util::*;
+
+pub mod autopilot;
+pub mod broadphase;
+pub mod clock;
+pub mod constants;
+pub mod director;
+pub mod editor;
+pub mod enemy;
+pub mod event;
+pub mod gate;
+pub mod geometry;
+pub mod ghost;
+pub mod head;
+pub mod input;
+pub mod interaction;
+pub mod leaderboard;
+pub mod level;
+pub mod mode;
+pub mod obstacle;
+pub mod popup;
+pub mod score;
+pub mod space;
+pub mod speed;
+pub mod stage;
+pub mod tail;
+pub mod title;
+pub mod ui;
+pub mod util;
+pub mod versus;
+
+// ゲーム本体のplugin一式。窓の有る本番と、窓の無いsoakの両方で使う
+pub struct GamePlugins;
+
+impl PluginGroup for GamePlugins {
+    fn build(&mut self, group: &mut PluginGroupBuilder) {
+        group
+            .add(event::ModPlugin {})
+            .add(stage::ModPlugin {})
+            .add(clock::ModPlugin {})
+            .add(space::ModPlugin {})
+            .add(level::ModPlugin {})
+            .add(mode::ModPlugin {})
+            .add(leaderboard::ModPlugin {})
+            .add(versus::ModPlugin {})
+            .add(editor::ModPlugin {})
+            .add(input::ModPlugin {})
+            .add(title::ModPlugin {})
+            .add(autopilot::ModPlugin {})
+            .add(score::ModPlugin {})
+            .add(ui::ModPlugin {})
+            .add(popup::ModPlugin {})
+            .add(speed::ModPlugin {})
+            .add(head::ModPlugin {})
+            .add(tail::ModPlugin {})
+            .add(gate::ModPlugin {})
+            .add(ghost::ModPlugin {})
+            .add(enemy::ModPlugin {})
+            .add(obstacle::ModPlugin {})
+            .add(broadphase::ModPlugin {})
+            .add(interaction::ModPlugin {});
+    }
+}
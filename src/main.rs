@@ -1,20 +1,4 @@
-pub use bevy::prelude::*;
-
-use crate::event::*;
-pub use constants::*;
-pub use util::*;
-
-mod constants;
-mod event;
-mod gate;
-mod head;
-mod input;
-mod interaction;
-mod space;
-mod stage;
-mod tail;
-mod ui;
-mod util;
+use space_centipede::*;
 
 fn main() {
     let mut app = App::build();
@@ -25,15 +9,7 @@ fn main() {
     #[cfg(target_arch = "wasm32")]
     app.add_plugin(bevy_webgl2::WebGL2Plugin);
 
-    app.add_plugin(event::ModPlugin {})
-        .add_plugin(stage::ModPlugin {})
-        .add_plugin(space::ModPlugin {})
-        .add_plugin(input::ModPlugin {})
-        .add_plugin(ui::ModPlugin {})
-        .add_plugin(head::ModPlugin {})
-        .add_plugin(tail::ModPlugin {})
-        .add_plugin(gate::ModPlugin {})
-        .add_plugin(interaction::ModPlugin {});
+    app.add_plugins(GamePlugins);
 
     app.run();
 }
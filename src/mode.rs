@@ -0,0 +1,286 @@
+use crate::*;
+
+pub struct ModPlugin;
+
+// 遊び方の選択と、遊び方ごとの終わり方
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Session>()
+            .add_system_to_stage(stage::PRE_UPDATE, select_mode_system.system())
+            .add_system_to_stage(stage::PRE_UPDATE, zen_speed_system.system())
+            .add_system_to_stage(stage::PRE_UPDATE, lock_seed_system.system())
+            .add_system_to_stage(
+                stage::POST_UPDATE,
+                time_limit_system.system().chain(void.system()),
+            )
+            .add_system_to_stage(
+                stage::SEND_EVENT,
+                decay_system.system().chain(void.system()),
+            )
+            // 始まった直後のSEND_EVENTで、前のゲームの時刻のまま尾を落とさないように先に進めておく
+            .add_system_to_stage(stage::LAST, on_game_start.system());
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum GameMode {
+    // 尾が無くなるまで
+    Endless,
+    // 制限時間内のスコアを競う
+    TimeAttack,
+    // 練習用。終わりがなく、尾は最低限まで生え直し、速さは自分で決める
+    Zen,
+    // 尾が時間とともにどんどん速く落ちていく
+    Survival,
+    // 二人で尾を奪い合う
+    Versus,
+}
+
+impl GameMode {
+    pub fn next(self) -> Self {
+        match self {
+            GameMode::Endless => GameMode::TimeAttack,
+            GameMode::TimeAttack => GameMode::Zen,
+            GameMode::Zen => GameMode::Survival,
+            GameMode::Survival => GameMode::Versus,
+            GameMode::Versus => GameMode::Endless,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameMode::Endless => "ENDLESS",
+            GameMode::TimeAttack => "TIME ATTACK",
+            GameMode::Zen => "ZEN",
+            GameMode::Survival => "SURVIVAL",
+            GameMode::Versus => "VERSUS",
+        }
+    }
+
+    // 尾が無くなったら終わるか
+    pub fn ends_without_tail(self) -> bool {
+        self != GameMode::TimeAttack && self != GameMode::Zen
+    }
+
+    // 記録を残すか
+    pub fn records_score(self) -> bool {
+        self != GameMode::Zen && self != GameMode::Versus
+    }
+
+    // 遊ぶのに必要な人数
+    pub fn min_players(self) -> usize {
+        match self {
+            GameMode::Versus => 2,
+            _ => 1,
+        }
+    }
+
+    // 頭同士がぶつかるか
+    pub fn heads_collide(self) -> bool {
+        self == GameMode::Versus
+    }
+
+    // 相手の尾を切ったら奪えるか
+    pub fn steals_tail(self) -> bool {
+        self == GameMode::Versus
+    }
+
+    // これより短くなったら尾が生え直す
+    pub fn min_tail(self) -> usize {
+        match self {
+            GameMode::Zen => ZEN_MIN_TAIL,
+            _ => 0,
+        }
+    }
+
+    // 尾が一つ落ちるまでの秒数。経過時間とともに短くなる
+    pub fn decay_interval(self, elapsed: f64) -> Option<f64> {
+        match self {
+            GameMode::Survival => Some(
+                (SURVIVAL_DECAY_SECONDS - elapsed * SURVIVAL_DECAY_ACCELERATION)
+                    .max(SURVIVAL_MIN_DECAY_SECONDS),
+            ),
+            _ => None,
+        }
+    }
+
+    pub fn time_limit(self) -> Option<f64> {
+        match self {
+            GameMode::TimeAttack => Some(TIME_ATTACK_SECONDS),
+            _ => None,
+        }
+    }
+
+    // 終わってから次を始めるまでの秒数。タイムアタックは結果を見せる
+    pub fn restart_delay(self) -> f64 {
+        match self {
+            GameMode::TimeAttack => TALLY_SECONDS,
+            _ => RESTART_SECONDS,
+        }
+    }
+}
+
+// 選択中の遊び方と、今回のゲームの開始時刻。speed_pinnedなら回っても速くならず、ブーストもブレーキも効かない
+pub struct Session {
+    pub mode: GameMode,
+    pub started_at: f64,
+    pub speed_pinned: bool,
+    // 次に尾が落ちる時刻
+    pub next_decay_at: f64,
+    // 今回のゲームのシードと、次も同じシードで遊ぶか
+    pub seed: u64,
+    pub seed_locked: bool,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            mode: GameMode::Endless,
+            started_at: 0.0,
+            speed_pinned: false,
+            next_decay_at: 0.0,
+            seed: 0,
+            seed_locked: false,
+        }
+    }
+}
+
+impl Session {
+    pub fn elapsed(&self, time: &clock::Clock) -> f64 {
+        time.seconds_since_startup() - self.started_at
+    }
+
+    // 次に尾が落ちるまでの秒数と、今の間隔。落ちない遊び方ならNone
+    pub fn decay(&self, time: &clock::Clock) -> Option<(f64, f64)> {
+        let interval = self.mode.decay_interval(self.elapsed(time))?;
+        Some((
+            (self.next_decay_at - time.seconds_since_startup()).max(0.0),
+            interval,
+        ))
+    }
+
+    // 残り時間。制限時間が無ければNone
+    pub fn remaining(&self, time: &clock::Clock) -> Option<f64> {
+        self.mode
+            .time_limit()
+            .map(|limit| (limit - self.elapsed(time)).max(0.0))
+    }
+}
+
+// Mで遊び方を切り替えて、最初からやり直す
+fn select_mode_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    editor: Res<editor::Editor>,
+    mut session: ResMut<Session>,
+    mut centipede_container: ResMut<CentipedeContainer>,
+    mut game_over_events: ResMut<Events<GameOver>>,
+) {
+    if editor.active || !keyboard_input.just_pressed(MODE_KEY) {
+        return;
+    }
+
+    session.mode = session.mode.next();
+    session.speed_pinned = session.mode == GameMode::Zen;
+
+    // 人数が足りなければキーボードで加わる
+    if centipede_container.players.len() < session.mode.min_players()
+        && !centipede_container
+            .players
+            .contains(&input::InputSource::Keyboard)
+    {
+        centipede_container
+            .players
+            .push(input::InputSource::Keyboard);
+    }
+    centipede_container.finish(0.0, GameOverCause::Aborted, &mut game_over_events);
+}
+
+// Rで今のシードを固定して、同じ門の並びで何度も遊べるようにする
+fn lock_seed_system(keyboard_input: Res<Input<KeyCode>>, mut session: ResMut<Session>) {
+    if keyboard_input.just_pressed(SEED_LOCK_KEY) {
+        session.seed_locked = !session.seed_locked;
+    }
+}
+
+// 禅モードでは上下キーで速さを変え、Lで固定を切り替える
+fn zen_speed_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut session: ResMut<Session>,
+    mut centipede_container: ResMut<CentipedeContainer>,
+) {
+    if session.mode != GameMode::Zen || !centipede_container.playing() {
+        return;
+    }
+
+    if keyboard_input.just_pressed(PIN_SPEED_KEY) {
+        session.speed_pinned = !session.speed_pinned;
+    }
+
+    let step = if keyboard_input.just_pressed(SPEED_UP_KEY) {
+        ZEN_SPEED_STEP
+    } else if keyboard_input.just_pressed(SPEED_DOWN_KEY) {
+        -ZEN_SPEED_STEP
+    } else {
+        0.0
+    };
+    for centipede in centipede_container.iter_alive_mut() {
+        centipede.speed = (centipede.speed + step).clamp(ZEN_MIN_SPEED, ZEN_MAX_SPEED);
+    }
+}
+
+// 制限時間が来たら終わり
+fn time_limit_system(
+    time: Res<clock::Clock>,
+    session: Res<Session>,
+    mut centipede_container: ResMut<CentipedeContainer>,
+    mut game_over_events: ResMut<Events<GameOver>>,
+) -> Option<()> {
+    if !centipede_container.playing() {
+        return None;
+    }
+
+    if session.remaining(&time)? <= 0.0 {
+        centipede_container.finish(
+            time.seconds_since_startup(),
+            GameOverCause::TimeUp,
+            &mut game_over_events,
+        );
+    }
+    None
+}
+
+// サバイバルでは時間が来るたびに尾を落とす
+fn decay_system(
+    time: Res<clock::Clock>,
+    mut session: ResMut<Session>,
+    centipede_container: Res<CentipedeContainer>,
+    mut decay_tail_events: ResMut<Events<DecayTail>>,
+) -> Option<()> {
+    if !centipede_container.playing() {
+        return None;
+    }
+    let interval = session.mode.decay_interval(session.elapsed(&time))?;
+
+    if time.seconds_since_startup() >= session.next_decay_at {
+        session.next_decay_at = time.seconds_since_startup() + interval;
+        for centipede in centipede_container.iter_alive() {
+            decay_tail_events.send(DecayTail {
+                player: centipede.player,
+            });
+        }
+    }
+    None
+}
+
+fn on_game_start(
+    time: Res<clock::Clock>,
+    mut session: ResMut<Session>,
+    (events, mut reader): (Res<Events<GameStart>>, Local<EventReader<GameStart>>),
+) {
+    for event in reader.iter(&events) {
+        session.started_at = time.seconds_since_startup();
+        session.seed = event.seed;
+        session.next_decay_at =
+            session.started_at + session.mode.decay_interval(0.0).unwrap_or(0.0);
+    }
+}
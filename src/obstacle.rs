@@ -0,0 +1,201 @@
+use crate::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+pub struct ModPlugin;
+
+// 盤面のパネルを塞ぐ障害物の配置
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ModResources>()
+            .init_resource::<Obstacles>()
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_game_start.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_level_edited.system());
+    }
+}
+
+struct ModResources {
+    mesh: Handle<Mesh>,
+    wall_material: Handle<StandardMaterial>,
+    asteroid_material: Handle<StandardMaterial>,
+}
+
+impl FromResources for ModResources {
+    fn from_resources(resources: &Resources) -> Self {
+        let mut meshes = resources.get_mut::<Assets<Mesh>>().unwrap();
+        let mut materials = resources.get_mut::<Assets<StandardMaterial>>().unwrap();
+
+        Self {
+            mesh: meshes.add(Mesh::from(shape::Cube {
+                size: OBSTACLE_SIZE,
+            })),
+            wall_material: materials.add(WALL_COLOR.into()),
+            asteroid_material: materials.add(ASTEROID_COLOR.into()),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum ObstacleKind {
+    // 頭が跳ね返る
+    Wall,
+    // 頭が激突して、柱と同じく尾が半分になる。ぶつかると壊れる
+    Asteroid,
+}
+
+pub struct Obstacle {}
+
+// パネルごとの障害物。衝突判定はここから引く
+#[derive(Default)]
+pub struct Obstacles {
+    pub board: Board,
+    panels: HashMap<Panel, (ObstacleKind, Entity)>,
+    // 今回のゲームで置いたパネル。壊れた後も残す
+    laid: HashSet<Panel>,
+}
+
+impl Obstacles {
+    pub fn get(&self, position: &Position) -> Option<ObstacleKind> {
+        let panel = self.board.panel(position)?;
+        self.panels.get(&panel).map(|(kind, _)| *kind)
+    }
+
+    pub fn contains(&self, position: &Position) -> bool {
+        self.get(position).is_some()
+    }
+
+    // 今回のゲームで障害物が置かれたパネルか。遊び方で壊れても変わらない
+    pub fn laid_on(&self, position: &Position) -> bool {
+        self.board
+            .panel(position)
+            .map_or(false, |panel| self.laid.contains(&panel))
+    }
+
+    // 障害物を取り除き、そのentityを返す
+    pub fn remove(&mut self, position: &Position) -> Option<Entity> {
+        let panel = self.board.panel(position)?;
+        self.panels.remove(&panel).map(|(_, entity)| entity)
+    }
+}
+
+fn on_game_start(
+    commands: &mut Commands,
+    resources: Res<ModResources>,
+    board: Res<Board>,
+    levels: Res<level::Levels>,
+    mut obstacles: ResMut<Obstacles>,
+    (events, mut reader): (Res<Events<GameStart>>, Local<EventReader<GameStart>>),
+    query: Query<Entity, With<Obstacle>>,
+) {
+    for event in reader.iter(&events) {
+        // ステージがあればその通りに置く
+        if place_level_obstacles(
+            commands,
+            &resources,
+            &board,
+            &levels,
+            &mut obstacles,
+            &query,
+        ) {
+            continue;
+        }
+
+        // 頭の出発地点の近くには置かない。同じシードなら同じ配置になる
+        let mut rng = event.rng(OBSTACLE_RNG_STREAM);
+        let start = levels.start().position();
+        while obstacles.panels.len() < OBSTACLE_COUNT {
+            let panel = board.random_panel(&mut rng);
+            if obstacles.panels.contains_key(&panel)
+                || board.center(&panel).distance(&start) <= OBSTACLE_NOT_SPAWN_DISTANCE_TO_HEAD
+            {
+                continue;
+            }
+
+            let kind = if rng.gen::<bool>() {
+                ObstacleKind::Wall
+            } else {
+                ObstacleKind::Asteroid
+            };
+            spawn_obstacle(commands, &resources, &mut obstacles, panel, kind);
+        }
+    }
+}
+
+// エディタで書き換えられたら置き直す
+fn on_level_edited(
+    commands: &mut Commands,
+    resources: Res<ModResources>,
+    board: Res<Board>,
+    levels: Res<level::Levels>,
+    mut obstacles: ResMut<Obstacles>,
+    (events, mut reader): (Res<Events<LevelEdited>>, Local<EventReader<LevelEdited>>),
+    query: Query<Entity, With<Obstacle>>,
+) {
+    if reader.iter(&events).last().is_some() {
+        place_level_obstacles(
+            commands,
+            &resources,
+            &board,
+            &levels,
+            &mut obstacles,
+            &query,
+        );
+    }
+}
+
+// 今ある障害物を消して、ステージの障害物を置く。ステージが無ければfalse
+fn place_level_obstacles(
+    commands: &mut Commands,
+    resources: &ModResources,
+    board: &Board,
+    levels: &level::Levels,
+    obstacles: &mut Obstacles,
+    query: &Query<Entity, With<Obstacle>>,
+) -> bool {
+    for entity in query.iter() {
+        commands.despawn_recursive(entity);
+    }
+    obstacles.panels.clear();
+    obstacles.laid.clear();
+    obstacles.board = *board;
+
+    match levels.current() {
+        Some(level) => {
+            for spec in &level.obstacles {
+                if board.contains(&spec.panel) {
+                    spawn_obstacle(commands, resources, obstacles, spec.panel, spec.kind);
+                }
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+fn spawn_obstacle(
+    commands: &mut Commands,
+    resources: &ModResources,
+    obstacles: &mut Obstacles,
+    panel: Panel,
+    kind: ObstacleKind,
+) {
+    let material = match kind {
+        ObstacleKind::Wall => resources.wall_material.clone(),
+        ObstacleKind::Asteroid => resources.asteroid_material.clone(),
+    };
+
+    let entity = commands
+        .spawn(PbrBundle {
+            mesh: resources.mesh.clone(),
+            material,
+            transform: Transform::from_translation(constants::INVISIBLE_POSITION),
+            ..Default::default()
+        })
+        .with(Obstacle {})
+        .with(obstacles.board.center(&panel))
+        .current_entity()
+        .unwrap();
+    obstacles.panels.insert(panel, (kind, entity));
+    obstacles.laid.insert(panel);
+}
@@ -0,0 +1,202 @@
+use crate::*;
+use std::collections::HashMap;
+
+pub struct ModPlugin;
+
+// 点が付いたり尾を失ったりした場所に、数字を浮かべて上へ流しながら消す
+// 失った尾の数はイベントに入っている前後の数で数える。scoreより後に置くこと
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ModResources>()
+            .add_system_to_stage(stage::LAST, on_score_awarded.system())
+            .add_system_to_stage(stage::LAST, on_miss.system())
+            .add_system_to_stage(stage::PRE_RENDER, popup_system.system());
+    }
+}
+
+struct ModResources {
+    font: Handle<Font>,
+}
+
+impl FromResources for ModResources {
+    fn from_resources(resources: &Resources) -> Self {
+        let asset_server = resources.get::<AssetServer>().unwrap();
+        Self {
+            font: asset_server.load(FONT),
+        }
+    }
+}
+
+// 浮かべた文字。positionは出た場所で、そこから上へ流れる
+pub struct Popup {
+    pub position: Position,
+    pub born: f64,
+    pub color: Color,
+}
+
+fn spawn_popup(
+    commands: &mut Commands,
+    resources: &ModResources,
+    time: &clock::Clock,
+    position: Position,
+    value: String,
+    color: Color,
+) {
+    commands
+        .spawn(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            text: Text {
+                value,
+                font: resources.font.clone(),
+                style: TextStyle {
+                    font_size: POPUP_SIZE,
+                    color,
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        })
+        .with(Popup {
+            position,
+            born: time.seconds_since_startup(),
+            color,
+        });
+}
+
+// 同じ場所で同じ刻みに付いた点はまとめて一つにし、門ならくぐったときの速さの倍率を添える
+// 今の速さは点が付いた後に変わっているかもしれないので使わない
+fn on_score_awarded(
+    commands: &mut Commands,
+    resources: Res<ModResources>,
+    time: Res<clock::Clock>,
+    (events, mut reader): (Res<Events<ScoreAwarded>>, Local<EventReader<ScoreAwarded>>),
+) {
+    let mut popups: Vec<(PlayerId, Position, isize, Option<f32>)> = vec![];
+    for event in reader.iter(&events) {
+        match popups
+            .iter_mut()
+            .find(|(player, position, _, _)| *player == event.player && *position == event.position)
+        {
+            Some((_, _, points, speed)) => {
+                *points += event.points;
+                *speed = speed.or(event.speed);
+            }
+            None => popups.push((event.player, event.position, event.points, event.speed)),
+        }
+    }
+
+    for (_, position, points, speed) in popups {
+        let multiplier = speed.map_or_else(String::new, |speed| format!("  x{:.1}", speed / 100.0));
+        let color = if points < 0 {
+            POPUP_LOSS_COLOR
+        } else {
+            POPUP_COLOR
+        };
+        spawn_popup(
+            commands,
+            &resources,
+            &time,
+            position,
+            format!("{:+}{:}", points, multiplier),
+            color,
+        );
+    }
+}
+
+// 激突と、尾を失ったときは赤く出す
+fn on_miss(
+    commands: &mut Commands,
+    resources: Res<ModResources>,
+    time: Res<clock::Clock>,
+    centipede_container: Res<CentipedeContainer>,
+    (crush_poll_events, mut crush_poll_reader): (
+        Res<Events<CrushPoll>>,
+        Local<EventReader<CrushPoll>>,
+    ),
+    (eat_tail_events, mut eat_tail_reader): (Res<Events<EatTail>>, Local<EventReader<EatTail>>),
+    (decay_tail_events, mut decay_tail_reader): (
+        Res<Events<DecayTail>>,
+        Local<EventReader<DecayTail>>,
+    ),
+) {
+    for event in crush_poll_reader.iter(&crush_poll_events) {
+        let lost = event.head.tails_before - event.head.tails_after;
+        spawn_popup(
+            commands,
+            &resources,
+            &time,
+            event.position,
+            format!("{:} -{:}", CRASH_TEXT, lost),
+            POPUP_LOSS_COLOR,
+        );
+    }
+
+    // 同じ刻みに同じ一匹の尾が何度切れても、一番根元で切れた分だけ失う
+    let mut cuts: HashMap<PlayerId, &EatTail> = HashMap::new();
+    for event in eat_tail_reader.iter(&eat_tail_events) {
+        let cut = cuts.entry(event.player).or_insert(event);
+        if event.tail_index < cut.tail_index {
+            *cut = event;
+        }
+    }
+    for cut in cuts.values() {
+        let lost = cut.tails_before.saturating_sub(cut.tail_index);
+        if lost > 0 {
+            spawn_popup(
+                commands,
+                &resources,
+                &time,
+                cut.position,
+                format!("-{:}", lost),
+                POPUP_LOSS_COLOR,
+            );
+        }
+    }
+
+    for event in decay_tail_reader.iter(&decay_tail_events) {
+        if let Some(centipede) = centipede_container
+            .alive(event.player)
+            .filter(|centipede| centipede.tail_count > 0)
+        {
+            spawn_popup(
+                commands,
+                &resources,
+                &time,
+                centipede.head(),
+                "-1".to_string(),
+                POPUP_LOSS_COLOR,
+            );
+        }
+    }
+}
+
+// 盤面の座標を画面の左下からの位置に直し、上へ流しながら薄くする。時間が来たら消す
+fn popup_system(
+    commands: &mut Commands,
+    time: Res<clock::Clock>,
+    windows: Res<Windows>,
+    mut popup_query: Query<(Entity, &Popup, &mut Style, &mut Text)>,
+) {
+    let window = windows
+        .get_primary()
+        .map(|window| (window.width(), window.height()));
+    for (entity, popup, mut style, mut text) in popup_query.iter_mut() {
+        let age = (time.seconds_since_startup() - popup.born) as f32;
+        if age >= POPUP_SECONDS {
+            commands.despawn(entity);
+            continue;
+        }
+
+        if let Some((width, height)) = window {
+            style.position.left = Val::Px(popup.position.x + width / 2.0);
+            style.position.bottom =
+                Val::Px(popup.position.y + POPUP_RISE_SPEED * age + height / 2.0);
+        }
+        let mut color = popup.color;
+        color.set_a(popup.color.a() * (1.0 - age / POPUP_SECONDS));
+        text.style.color = color;
+    }
+}
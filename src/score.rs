@@ -0,0 +1,143 @@
+use crate::*;
+use std::collections::HashMap;
+
+pub struct ModPlugin;
+
+// 点の決まり。遊び方ごとの決まりがゲームのイベントを点に換え、ScoreAwardedで知らせる
+// 表示や記録はScoreAwardedだけを見る。uiより前に置くこと
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Scoring>()
+            .add_system_to_stage(stage::LAST, on_through_gate.system())
+            .add_system_to_stage(stage::LAST, on_eat_tail.system());
+    }
+}
+
+// 点と、その理由
+pub type Award = (isize, ScoreReason);
+
+// 遊び方ごとの点の決まり。点が付かなければ空を返す
+pub trait ScoringRules: Send + Sync {
+    // 門をくぐった。くぐったときの速さや尾の数はeventに入っている
+    fn through_gate(&self, event: &ThroughGate) -> Vec<Award>;
+
+    // event.eaterがevent.playerの尾を切った
+    fn eat_tail(&self, _event: &EatTail) -> Vec<Award> {
+        vec![]
+    }
+}
+
+// 門をくぐると伸びた後の尾の数だけ点が入り、速さが100を超えた分だけ上乗せされる
+pub struct StandardRules;
+
+impl ScoringRules for StandardRules {
+    fn through_gate(&self, event: &ThroughGate) -> Vec<Award> {
+        let gate = event.head.tails_after as isize;
+        let total = (event.head.tails_after as f32 * event.head.speed / 100.0).floor() as isize;
+        vec![
+            (gate, ScoreReason::Gate),
+            (total - gate, ScoreReason::Speed),
+        ]
+    }
+}
+
+// 対戦では、相手の尾を切ると奪った尾の数だけ点が入る
+pub struct VersusRules;
+
+impl ScoringRules for VersusRules {
+    fn through_gate(&self, event: &ThroughGate) -> Vec<Award> {
+        StandardRules.through_gate(event)
+    }
+
+    fn eat_tail(&self, event: &EatTail) -> Vec<Award> {
+        if event.eater.map_or(true, |eater| eater == event.player) {
+            return vec![];
+        }
+        let stolen = event.tails_before.saturating_sub(event.tail_index);
+        vec![((stolen * VERSUS_STEAL_POINTS) as isize, ScoreReason::Steal)]
+    }
+}
+
+// 遊び方ごとに登録された決まり。登録の無い遊び方はStandardRules
+pub struct Scoring {
+    rules: HashMap<mode::GameMode, Box<dyn ScoringRules>>,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        let mut scoring = Self {
+            rules: HashMap::new(),
+        };
+        scoring.register(mode::GameMode::Versus, VersusRules);
+        scoring
+    }
+}
+
+impl Scoring {
+    pub fn register(&mut self, mode: mode::GameMode, rules: impl ScoringRules + 'static) {
+        self.rules.insert(mode, Box::new(rules));
+    }
+
+    pub fn rules(&self, mode: mode::GameMode) -> &dyn ScoringRules {
+        self.rules
+            .get(&mode)
+            .map_or(&StandardRules, |rules| rules.as_ref())
+    }
+}
+
+// 点の付かない理由は知らせない
+fn send(
+    awarded_events: &mut Events<ScoreAwarded>,
+    player: PlayerId,
+    position: Position,
+    speed: Option<f32>,
+    awards: Vec<Award>,
+) {
+    for (points, reason) in awards.into_iter().filter(|(points, _)| *points != 0) {
+        awarded_events.send(ScoreAwarded {
+            player,
+            points,
+            reason,
+            position,
+            speed,
+        });
+    }
+}
+
+fn on_through_gate(
+    session: Res<mode::Session>,
+    scoring: Res<Scoring>,
+    mut awarded_events: ResMut<Events<ScoreAwarded>>,
+    (events, mut reader): (Res<Events<ThroughGate>>, Local<EventReader<ThroughGate>>),
+) {
+    let rules = scoring.rules(session.mode);
+    for event in reader.iter(&events) {
+        send(
+            &mut awarded_events,
+            event.player,
+            event.position,
+            Some(event.head.speed),
+            rules.through_gate(event),
+        );
+    }
+}
+
+fn on_eat_tail(
+    session: Res<mode::Session>,
+    scoring: Res<Scoring>,
+    mut awarded_events: ResMut<Events<ScoreAwarded>>,
+    (events, mut reader): (Res<Events<EatTail>>, Local<EventReader<EatTail>>),
+) {
+    let rules = scoring.rules(session.mode);
+    for event in reader.iter(&events) {
+        if let Some(eater) = event.eater {
+            send(
+                &mut awarded_events,
+                eater,
+                event.position,
+                None,
+                rules.eat_tail(event),
+            );
+        }
+    }
+}
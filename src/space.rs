@@ -4,17 +4,40 @@ pub struct ModPlugin;
 
 impl Plugin for ModPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_startup_system(setup_system.system())
+        app.init_resource::<Board>()
+            .init_resource::<ModResources>()
+            .add_startup_system(setup_system.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_game_start.system())
             .add_system_to_stage(stage::PRE_RENDER, position_to_translation_system.system());
     }
 }
 
-// 背景の碁盤目状のパネルと、ライト、カメラ等を生成
-fn setup_system(
-    commands: &mut Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
+struct ModResources {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+impl FromResources for ModResources {
+    fn from_resources(resources: &Resources) -> Self {
+        Self {
+            mesh: resources
+                .get_mut::<Assets<Mesh>>()
+                .unwrap()
+                .add(Mesh::from(shape::Plane {
+                    size: PANEL_SIZE - 2.0,
+                })),
+            material: resources
+                .get_mut::<Assets<StandardMaterial>>()
+                .unwrap()
+                .add(PANEL_COLOR.into()),
+        }
+    }
+}
+
+struct BoardPanel {}
+
+// ライト、カメラ等を生成
+fn setup_system(commands: &mut Commands) {
     commands
         .spawn(LightBundle {
             transform: Transform::from_translation(LIGHT_COORDINATE),
@@ -28,44 +51,42 @@ fn setup_system(
                 .looking_at(Vec3::default(), Vec3::unit_y()),
             ..Default::default()
         });
+}
 
-    let mesh = meshes.add(Mesh::from(shape::Plane {
-        size: PANEL_SIZE - 2.0,
-    }));
-
-    let material = materials.add(PANEL_COLOR.into());
+// 背景の碁盤目状のパネルを、選ばれている盤面の大きさで作り直す
+fn on_game_start(
+    commands: &mut Commands,
+    board: Res<Board>,
+    resources: Res<ModResources>,
+    (events, mut reader): (Res<Events<GameStart>>, Local<EventReader<GameStart>>),
+    query: Query<Entity, With<BoardPanel>>,
+) {
+    for _ in reader.iter(&events) {
+        for entity in query.iter() {
+            commands.despawn_recursive(entity);
+        }
 
-    for x in 0..PANEL_X_COUNT {
-        for y in 0..PANEL_Y_COUNT {
-            block(
-                commands,
-                mesh.clone(),
-                material.clone(),
-                x - (PANEL_X_COUNT - 1) / 2,
-                y - (PANEL_Y_COUNT - 1) / 2,
-            );
+        for x in 0..board.x {
+            for y in 0..board.y {
+                block(commands, &resources, board.center(&Panel { x, y }));
+            }
         }
     }
 }
 
-fn block(
-    commands: &mut Commands,
-    mesh: Handle<Mesh>,
-    material: Handle<StandardMaterial>,
-    x: isize,
-    y: isize,
-) {
-    // mesh以外はコピー
-    commands.spawn(PbrBundle {
-        mesh,
-        material,
-        transform: Transform {
-            translation: Vec3::new(x as f32 * PANEL_SIZE, y as f32 * PANEL_SIZE, 1.),
-            rotation: Quat::from_rotation_x(-30.),
+fn block(commands: &mut Commands, resources: &ModResources, center: Position) {
+    commands
+        .spawn(PbrBundle {
+            mesh: resources.mesh.clone(),
+            material: resources.material.clone(),
+            transform: Transform {
+                translation: Vec3::new(center.x, center.y, 1.),
+                rotation: Quat::from_rotation_x(-30.),
+                ..Default::default()
+            },
             ..Default::default()
-        },
-        ..Default::default()
-    });
+        })
+        .with(BoardPanel {});
 }
 
 // positionをtranslationに変換
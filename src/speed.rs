@@ -0,0 +1,141 @@
+use crate::*;
+use std::collections::HashMap;
+
+pub struct ModPlugin;
+
+// 頭の速さの決まり。回ると速くなり、まっすぐ進むと元に戻っていく
+// ブレーキはスコアか尾と引き換えに減速し、ブースト(加速)は一度使うとしばらく使えない
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<SpeedModel>()
+            .init_resource::<Throttles>()
+            .add_system_to_stage(stage::LAST, on_game_start.system())
+            .add_system(speed_system.system());
+    }
+}
+
+// 速さの決まり。遊び方や試験で変えられる
+pub struct SpeedModel {
+    // 回っても、これより速くはならない
+    pub cap: f32,
+    // まっすぐ進んでいる間、一秒で落ちる速さ。DEFAULT_SPEEDより下にはならない
+    pub decay: f32,
+}
+
+impl Default for SpeedModel {
+    fn default() -> Self {
+        Self {
+            cap: SPEED_CAP,
+            decay: SPEED_DECAY,
+        }
+    }
+}
+
+// 一匹ごとのブレーキとブーストの状態
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Throttle {
+    // 次の支払いまでに、ブレーキで落とした速さ
+    pub braked: f32,
+    pub boost_ready_at: f64,
+    boost_pressed: bool,
+}
+
+impl Throttle {
+    // 次の支払いまでの割合。0.0〜1.0
+    pub fn brake_ratio(&self) -> f32 {
+        self.braked / BRAKE_SPEED_PER_COST
+    }
+
+    // ブーストがまた使えるようになるまでの割合。使えるなら1.0
+    pub fn boost_ratio(&self, now: f64) -> f32 {
+        (1.0 - (self.boost_ready_at - now) / BOOST_COOLDOWN_SECONDS).clamp(0.0, 1.0) as f32
+    }
+}
+
+#[derive(Default)]
+pub struct Throttles {
+    pub players: HashMap<PlayerId, Throttle>,
+}
+
+impl Throttles {
+    pub fn get(&self, player: PlayerId) -> Throttle {
+        self.players.get(&player).copied().unwrap_or_default()
+    }
+}
+
+fn on_game_start(
+    mut throttles: ResMut<Throttles>,
+    (events, mut reader): (Res<Events<GameStart>>, Local<EventReader<GameStart>>),
+) {
+    for _ in reader.iter(&events) {
+        *throttles = Throttles::default();
+    }
+}
+
+fn speed_system(
+    time: Res<clock::Clock>,
+    controls: Res<input::Controls>,
+    model: Res<SpeedModel>,
+    session: Res<mode::Session>,
+    mut throttles: ResMut<Throttles>,
+    status: Res<ui::Status>,
+    mut centipede_container: ResMut<CentipedeContainer>,
+    mut decay_tail_events: ResMut<Events<DecayTail>>,
+    mut awarded_events: ResMut<Events<ScoreAwarded>>,
+) {
+    let delta_seconds = time.delta_seconds();
+    for centipede in centipede_container.iter_alive_mut() {
+        let control = controls.get(centipede.input);
+        let throttle = throttles.players.entry(centipede.player).or_default();
+        // 払った点が反映されるのはscoreの後なので、この刻みで払った分を引いておく
+        let mut score = status.get(centipede.player).score;
+
+        // 禅モードで速さを固定している間は、勝手にもブーストやブレーキでも変わらず、点も尾も払わない
+        if session.speed_pinned {
+            throttle.boost_pressed = control.boost;
+            continue;
+        }
+
+        if centipede.speed > model.cap {
+            // ブーストで上限を超えた分は、回っていても戻っていく
+            centipede.speed = (centipede.speed - BOOST_DECAY * delta_seconds).max(model.cap);
+        } else if centipede.movement.orbit().is_some() {
+            centipede.speed = (centipede.speed + SPEED_UP * delta_seconds).min(model.cap);
+        } else if centipede.speed > DEFAULT_SPEED {
+            centipede.speed = (centipede.speed - model.decay * delta_seconds).max(DEFAULT_SPEED);
+        }
+
+        // 押した瞬間だけ加速し、しばらく使えなくなる
+        let now = time.seconds_since_startup();
+        if control.boost && !throttle.boost_pressed && now >= throttle.boost_ready_at {
+            centipede.speed += BOOST_SPEED;
+            throttle.boost_ready_at = now + BOOST_COOLDOWN_SECONDS;
+        }
+        throttle.boost_pressed = control.boost;
+
+        // 落とした速さがたまるごとに、スコアで払う。スコアが足りなければ尾で払う
+        if control.brake && centipede.speed > BRAKE_MIN_SPEED {
+            let braked =
+                (BRAKE_DECELERATION * delta_seconds).min(centipede.speed - BRAKE_MIN_SPEED);
+            centipede.speed -= braked;
+            throttle.braked += braked;
+            while throttle.braked >= BRAKE_SPEED_PER_COST {
+                throttle.braked -= BRAKE_SPEED_PER_COST;
+                if score >= BRAKE_SCORE_COST {
+                    score -= BRAKE_SCORE_COST;
+                    awarded_events.send(ScoreAwarded {
+                        player: centipede.player,
+                        points: -(BRAKE_SCORE_COST as isize),
+                        reason: ScoreReason::Brake,
+                        position: centipede.head(),
+                        speed: None,
+                    });
+                } else {
+                    decay_tail_events.send(DecayTail {
+                        player: centipede.player,
+                    });
+                }
+            }
+        }
+    }
+}
@@ -5,6 +5,8 @@ pub const PRE_UPDATE: &str = bevy::prelude::stage::PRE_UPDATE;
 pub const UPDATE: &str = bevy::prelude::stage::UPDATE;
 pub const POST_UPDATE: &str = bevy::prelude::stage::POST_UPDATE;
 pub const LAST: &str = bevy::prelude::stage::LAST;
+// 当たり判定の升目を作り直す。全部動き終わってから、SEND_EVENTの当たり判定より前
+pub const BROADPHASE: &str = "BROADPHASE";
 pub const SEND_EVENT: &str = "SEND_EVENT";
 pub const RECEIVE_EVENT: &str = "RECEIVE_EVENT";
 pub const PRE_RENDER: &str = "PRE_RENDER";
@@ -13,7 +15,8 @@ pub struct ModPlugin;
 
 impl Plugin for ModPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_stage_after(stage::LAST, SEND_EVENT, SystemStage::serial())
+        app.add_stage_after(stage::LAST, BROADPHASE, SystemStage::serial())
+            .add_stage_after(BROADPHASE, SEND_EVENT, SystemStage::serial())
             .add_stage_after(SEND_EVENT, RECEIVE_EVENT, SystemStage::serial())
             .add_stage_after(RECEIVE_EVENT, PRE_RENDER, SystemStage::serial());
     }
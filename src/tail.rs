@@ -5,18 +5,18 @@ pub struct ModPlugin;
 impl Plugin for ModPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<ModResources>()
-            .add_system_to_stage(
-                stage::POST_UPDATE,
-                move_tail_system.system().chain(void.system()),
-            )
+            .add_system_to_stage(stage::POST_UPDATE, move_tail_system.system())
             .add_system_to_stage(stage::POST_UPDATE, purged_tail_system.system())
             .add_system_to_stage(stage::POST_UPDATE, rotate_tail_system.system())
-            .add_system_to_stage(stage::RECEIVE_EVENT, on_game_start.system())
-            .add_system_to_stage(
-                stage::RECEIVE_EVENT,
-                on_through_gate.system().chain(void.system()),
-            )
-            .add_system_to_stage(stage::RECEIVE_EVENT, on_miss.system().chain(void.system()));
+            .add_system_to_stage(stage::POST_UPDATE, regrow_tail_system.system())
+            // 頭と同じLASTで尾を生やし、始まった直後のイベントが届く前に揃えておく
+            // 前のゲームの尾を切り離すのはその前。生やした尾はstageの終わりまで現れないので巻き込まない
+            .add_system_to_stage(stage::LAST, on_game_over.system())
+            .add_system_to_stage(stage::LAST, on_game_start.system())
+            // 生やした尾はstageの終わりまで現れないので、切り離しを先に済ませてから生やす
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_decay_tail.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_miss.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_through_gate.system());
     }
 }
 
@@ -25,7 +25,8 @@ struct ModResources {
     base_quaternion: Quat,
     spin_axis: Vec3,
     mesh: Handle<Mesh>,
-    material: Handle<StandardMaterial>,
+    // プレイヤーごとの色
+    materials: Vec<Handle<StandardMaterial>>,
     purged_material: Handle<StandardMaterial>,
 }
 
@@ -55,18 +56,28 @@ impl FromResources for ModResources {
             }
             .normalize(),
             mesh: meshes.add(Mesh::from(shape::Cube { size: TAIL_SIZE })),
-            material: materials.add(TAIL_COLOR.into()),
+            materials: PLAYER_COLORS
+                .iter()
+                .map(|color| materials.add((*color).into()))
+                .collect(),
             purged_material: materials.add(PURGED_COLOR.into()),
         }
     }
 }
 
+impl ModResources {
+    fn material(&self, player: PlayerId) -> Handle<StandardMaterial> {
+        self.materials[player.0 % self.materials.len()].clone()
+    }
+}
+
 struct Spinner {
     direction: Vec2,
     margin: f64,
 }
 
 pub struct LivingTail {
+    pub player: PlayerId,
     pub index: usize,
 }
 
@@ -78,11 +89,39 @@ struct PurgedTail {
 fn on_game_start(
     commands: &mut Commands,
     resources: Res<ModResources>,
+    centipede_container: Res<CentipedeContainer>,
     (events, mut reader): (Res<Events<GameStart>>, Local<EventReader<GameStart>>),
 ) {
     for _ in reader.iter(&events) {
-        for i in 0..INITIAL_CENTIPEDE_LENGTH {
-            spawn_tail(commands, &resources, i);
+        for centipede in centipede_container.iter_alive() {
+            for i in 0..INITIAL_CENTIPEDE_LENGTH {
+                spawn_tail(commands, &resources, centipede.player, i);
+            }
+        }
+    }
+}
+
+// 尾が残ったまま終わった場合(クリアやステージ選択)は、その一匹の尾を全部切り離す
+fn on_game_over(
+    commands: &mut Commands,
+    time: Res<clock::Clock>,
+    resources: Res<ModResources>,
+    (events, mut reader): (Res<Events<GameOver>>, Local<EventReader<GameOver>>),
+    query: Query<(Entity, &LivingTail)>,
+) {
+    for event in reader.iter(&events) {
+        for (entity, _) in query.iter().filter(|(_, tail)| tail.player == event.player) {
+            commands.remove::<(LivingTail, Handle<StandardMaterial>)>(entity);
+            commands.insert(
+                entity,
+                (
+                    PurgedTail {
+                        remove_at: time.seconds_since_startup() + 2.5,
+                        speed: DEFAULT_SPEED,
+                    },
+                    resources.purged_material.clone(),
+                ),
+            );
         }
     }
 }
@@ -92,39 +131,73 @@ fn on_through_gate(
     mut centipede_container: ResMut<CentipedeContainer>,
     resources: Res<ModResources>,
     (events, mut reader): (Res<Events<ThroughGate>>, Local<EventReader<ThroughGate>>),
-) -> Option<()> {
-    let mut centipede = centipede_container.alive_mut()?;
+) {
+    for event in reader.iter(&events) {
+        if let Some(centipede) = centipede_container.alive_mut(event.player) {
+            spawn_tail(commands, &resources, centipede.player, centipede.tail_count);
+            centipede.tail_count += 1;
+        }
+    }
+}
 
-    for _ in reader.iter(&events) {
-        spawn_tail(commands, &resources, centipede.tail_count);
-        centipede.tail_count += 1;
+// 尾の最低数が決まっていれば、そこまで少しずつ生やす
+fn regrow_tail_system(
+    commands: &mut Commands,
+    time: Res<clock::Clock>,
+    session: Res<mode::Session>,
+    resources: Res<ModResources>,
+    mut centipede_container: ResMut<CentipedeContainer>,
+    mut last_regrow: Local<f64>,
+) {
+    if time.seconds_since_startup() - *last_regrow < ZEN_REGROW_SECONDS {
+        return;
+    }
+
+    for centipede in centipede_container.iter_alive_mut() {
+        if centipede.tail_count < session.mode.min_tail() {
+            *last_regrow = time.seconds_since_startup();
+            spawn_tail(commands, &resources, centipede.player, centipede.tail_count);
+            centipede.tail_count += 1;
+        }
     }
-    None
 }
 
-fn spawn_tail(commands: &mut Commands, resources: &Res<ModResources>, index: usize) {
+fn spawn_tail(
+    commands: &mut Commands,
+    resources: &Res<ModResources>,
+    player: PlayerId,
+    index: usize,
+) {
     commands
         .spawn(PbrBundle {
             mesh: resources.mesh.clone(),
-            material: resources.material.clone(),
+            material: resources.material(player),
             transform: Transform {
                 translation: constants::INVISIBLE_POSITION,
                 ..Default::default()
             },
+            // 次に位置が反映されるまでの間、原点で頭にぶつからないように
+            global_transform: GlobalTransform::from_translation(constants::INVISIBLE_POSITION),
             ..Default::default()
         })
         .with(Position::default(false))
-        .with(LivingTail { index })
+        .with(LivingTail { player, index })
         .with(Spinner {
             direction: Vec2 { x: 0.0, y: 0.0 },
             margin: index as f64 * 0.2,
         });
 }
 
+// 柱に激突すると、尾が半分になる
+pub fn tails_after_crush(tail_count: usize) -> usize {
+    (tail_count as f32 / 2.0).floor() as usize
+}
+
 fn on_miss(
     commands: &mut Commands,
     mut centipede_container: ResMut<CentipedeContainer>,
-    time: Res<Time>,
+    time: Res<clock::Clock>,
+    session: Res<mode::Session>,
     resources: Res<ModResources>,
     (eat_tail_events, mut eat_tail_reader): (Res<Events<EatTail>>, Local<EventReader<EatTail>>),
     (crush_poll_events, mut crush_poll_reader): (
@@ -132,26 +205,31 @@ fn on_miss(
         Local<EventReader<CrushPoll>>,
     ),
     mut living_tail_query: Query<(Entity, &LivingTail)>,
-) -> Option<()> {
-    let mut centipede = centipede_container.alive_mut()?;
-
-    for _ in crush_poll_reader.iter(&crush_poll_events) {
+) {
+    for event in crush_poll_reader.iter(&crush_poll_events) {
+        let centipede = match centipede_container.alive_mut(event.player) {
+            Some(centipede) => centipede,
+            None => continue,
+        };
         let original_count = centipede.tail_count;
-        centipede.tail_count = (centipede.tail_count as f32 / 2.0).floor() as usize;
+        centipede.tail_count = tails_after_crush(centipede.tail_count);
         purge_tail(
             commands,
             &time,
             &resources,
-            &centipede,
+            centipede,
             original_count,
             &mut living_tail_query,
         )
     }
 
+    // 奪った尾は、全員分の切り離しが済んでから生やす
+    let mut steals = vec![];
     for event in eat_tail_reader.iter(&eat_tail_events) {
-        if event.tail_index >= centipede.tail_count {
-            continue;
-        }
+        let centipede = match centipede_container.alive_mut(event.player) {
+            Some(centipede) if event.tail_index < centipede.tail_count => centipede,
+            _ => continue,
+        };
         let original_count = centipede.tail_count;
         centipede.tail_count = event.tail_index;
 
@@ -159,17 +237,60 @@ fn on_miss(
             commands,
             &time,
             &resources,
-            &centipede,
+            centipede,
+            original_count,
+            &mut living_tail_query,
+        );
+
+        // 対戦では相手の尾を切ったら、切った分だけ自分の尾になる
+        if let Some(eater) = event
+            .eater
+            .filter(|eater| *eater != event.player && session.mode.steals_tail())
+        {
+            steals.push((eater, original_count - event.tail_index));
+        }
+    }
+
+    for (eater, stolen) in steals {
+        if let Some(eater) = centipede_container.alive_mut(eater) {
+            for _ in 0..stolen {
+                spawn_tail(commands, &resources, eater.player, eater.tail_count);
+                eater.tail_count += 1;
+            }
+        }
+    }
+}
+
+// 一番後ろの尾を一つ切り離す
+fn on_decay_tail(
+    commands: &mut Commands,
+    mut centipede_container: ResMut<CentipedeContainer>,
+    time: Res<clock::Clock>,
+    resources: Res<ModResources>,
+    (events, mut reader): (Res<Events<DecayTail>>, Local<EventReader<DecayTail>>),
+    mut living_tail_query: Query<(Entity, &LivingTail)>,
+) {
+    for event in reader.iter(&events) {
+        let centipede = match centipede_container.alive_mut(event.player) {
+            Some(centipede) => centipede,
+            None => continue,
+        };
+        let original_count = centipede.tail_count;
+        centipede.tail_count = centipede.tail_count.saturating_sub(1);
+        purge_tail(
+            commands,
+            &time,
+            &resources,
+            centipede,
             original_count,
             &mut living_tail_query,
         )
     }
-    None
 }
 
 fn purge_tail(
     commands: &mut Commands,
-    time: &Time,
+    time: &clock::Clock,
     resources: &ModResources,
     centipede: &Alive,
     original_count: usize,
@@ -180,7 +301,7 @@ fn purge_tail(
     }
 
     for (entity, tail) in tail_query.iter_mut() {
-        if tail.index < centipede.tail_count {
+        if tail.player != centipede.player || tail.index < centipede.tail_count {
             continue;
         }
         let purged_index_ratio = if original_count > tail.index {
@@ -207,59 +328,67 @@ fn purge_tail(
 fn move_tail_system(
     centipede_container: Res<CentipedeContainer>,
     mut tail_query: Query<(&mut Position, &LivingTail, &mut Spinner)>,
-) -> Option<()> {
-    let centipede = centipede_container.alive()?;
+) {
+    for centipede in centipede_container.iter_alive() {
+        move_tail(centipede, &mut tail_query);
+    }
+}
 
+// 頭の通った跡に沿って、尾を等間隔に並べる
+fn move_tail(
+    centipede: &Alive,
+    tail_query: &mut Query<(&mut Position, &LivingTail, &mut Spinner)>,
+) {
     let mut tail_positions = vec![];
     let mut prev_position = None;
     let mut distance = 0.0;
 
     'outer: for position in centipede.position_history.iter().rev() {
-        match prev_position {
-            Some(prev) => {
-                let current_distance = position.distance(&prev);
-                distance += current_distance;
-                while distance >= TAIL_DISTANCE {
-                    distance = distance - TAIL_DISTANCE;
-                    tail_positions.push(prev.forward_to(position, current_distance - distance));
-                    if tail_positions.len() > centipede.tail_count {
-                        break 'outer;
-                    }
+        if let Some(prev) = prev_position {
+            let current_distance = position.distance(&prev);
+            distance += current_distance;
+            while distance >= TAIL_DISTANCE {
+                distance -= TAIL_DISTANCE;
+                tail_positions.push(prev.forward_to(position, current_distance - distance));
+                if tail_positions.len() > centipede.tail_count {
+                    break 'outer;
                 }
             }
-            None => {}
         }
-        prev_position = Some(position.clone());
+        prev_position = Some(*position);
     }
 
     for (mut position, tail, mut spinner) in tail_query.iter_mut() {
-        match tail_positions.get(tail.index) {
-            Some(tail_position) => {
-                spinner.direction = Vec2 {
-                    x: tail_position.x - position.x,
-                    y: tail_position.y - position.y,
-                };
-                position.x = tail_position.x;
-                position.y = tail_position.y;
-                position.visible = true;
-            }
-            None => {}
+        if tail.player != centipede.player {
+            continue;
+        }
+        if let Some(tail_position) = tail_positions.get(tail.index) {
+            spinner.direction = Vec2 {
+                x: tail_position.x - position.x,
+                y: tail_position.y - position.y,
+            };
+            position.x = tail_position.x;
+            position.y = tail_position.y;
+            position.visible = true;
         }
     }
-    None
 }
 
 fn rotate_tail_system(
-    time: Res<Time>,
+    time: Res<clock::Clock>,
     rotations: Res<ModResources>,
     mut query: Query<(&mut Transform, &Spinner)>,
 ) {
     for (mut transform, spinner) in query.iter_mut() {
-        // 進行方向に合わせる
-        let to_forward = Quat::from_axis_angle(
-            rotations.forward_axis,
-            spinner.direction.angle_between(Vec2 { x: 1.0, y: 1.0 }),
-        );
+        // 進行方向に合わせる。まだ一度も動いていなければ向きは無い
+        let to_forward = if spinner.direction == (Vec2 { x: 0.0, y: 0.0 }) {
+            Quat::identity()
+        } else {
+            Quat::from_axis_angle(
+                rotations.forward_axis,
+                spinner.direction.angle_between(Vec2 { x: 1.0, y: 1.0 }),
+            )
+        };
 
         // 頂点が前方に来るように
         let tilt = rotations.base_quaternion;
@@ -277,13 +406,13 @@ fn rotate_tail_system(
 
 fn purged_tail_system(
     commands: &mut Commands,
-    time: Res<Time>,
+    time: Res<clock::Clock>,
     mut query: Query<(Entity, &mut Position, &mut Spinner, &PurgedTail)>,
 ) {
     for (entity, mut position, spinner, purged_tail) in query.iter_mut() {
         if time.seconds_since_startup() > purged_tail.remove_at {
             commands.despawn_recursive(entity);
-        } else {
+        } else if spinner.direction != (Vec2 { x: 0.0, y: 0.0 }) {
             position.move_to_with_sec(spinner.direction, purged_tail.speed, time.delta_seconds());
         }
     }
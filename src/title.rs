@@ -0,0 +1,53 @@
+use crate::*;
+
+pub struct ModPlugin;
+
+// タイトル画面。起動直後はbotのデモを流し、何か押したら本番を始める
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Attract>()
+            .add_system_to_stage(stage::PRE_UPDATE, press_start_system.system());
+    }
+}
+
+// デモを流しているか
+pub struct Attract {
+    pub active: bool,
+}
+
+impl Default for Attract {
+    fn default() -> Self {
+        Self { active: true }
+    }
+}
+
+impl Attract {
+    // 次のゲームに参加する入力元。デモ中はbotだけ
+    pub fn players(&self, centipede_container: &CentipedeContainer) -> Vec<input::InputSource> {
+        if self.active {
+            vec![input::InputSource::Bot(ATTRACT_BOT)]
+        } else {
+            centipede_container.players.clone()
+        }
+    }
+}
+
+// キーかマウスのボタンが押されたら、デモを打ち切ってすぐに始め直す
+// 押したキーはそのまま普段の操作にもなる(数字キーならそのステージで始まる)
+fn press_start_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut attract: ResMut<Attract>,
+    mut centipede_container: ResMut<CentipedeContainer>,
+    mut game_over_events: ResMut<Events<GameOver>>,
+) {
+    if !attract.active
+        || keyboard_input.get_just_pressed().next().is_none()
+            && mouse_input.get_just_pressed().next().is_none()
+    {
+        return;
+    }
+
+    attract.active = false;
+    centipede_container.finish(0.0, GameOverCause::Aborted, &mut game_over_events);
+}
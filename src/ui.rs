@@ -1,18 +1,28 @@
 use crate::*;
 use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
+use std::collections::HashMap;
 
 pub struct ModPlugin;
 
 // スコア等の文字表示
 impl Plugin for ModPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_plugin(FrameTimeDiagnosticsPlugin::default())
+        app.add_plugin(FrameTimeDiagnosticsPlugin)
             .init_resource::<Status>()
             .add_startup_system(setup.system())
             .add_system_to_stage(stage::LAST, on_game_start.system())
             .add_system_to_stage(stage::LAST, on_through_gate.system())
+            .add_system_to_stage(stage::LAST, on_score_awarded.system())
             .add_system_to_stage(stage::PRE_RENDER, score_update_system.system())
-            .add_system_to_stage(stage::PRE_RENDER, fps_update_system.system());
+            .add_system_to_stage(stage::PRE_RENDER, gauge_update_system.system())
+            .add_system_to_stage(stage::PRE_RENDER, fps_update_system.system())
+            .add_system_to_stage(stage::PRE_RENDER, level_update_system.system())
+            .add_system_to_stage(stage::PRE_RENDER, editor_update_system.system())
+            .add_system_to_stage(stage::PRE_RENDER, mode_update_system.system())
+            .add_system_to_stage(stage::PRE_RENDER, tally_update_system.system())
+            .add_system_to_stage(stage::PRE_RENDER, round_update_system.system())
+            .add_system_to_stage(stage::PRE_RENDER, title_update_system.system())
+            .add_system_to_stage(stage::PRE_RENDER, ghost_update_system.system());
     }
 }
 
@@ -20,10 +30,61 @@ struct FpsText;
 
 struct ScoreText;
 
+struct GaugeText;
+
+struct LevelText;
+
+struct EditorText;
+
+struct ModeText;
+
+struct TallyText;
+
+struct RoundText;
+
+struct TitleText;
+
+struct GhostText;
+
+// 一匹ごとの今回のゲームのスコアと、くぐった門の数
+#[derive(Copy, Clone, Default, Debug)]
+pub struct PlayerStatus {
+    pub score: usize,
+    pub gates: usize,
+}
+
 #[derive(Default)]
-struct Status {
-    score: usize,
-    high_score: usize,
+pub struct Status {
+    pub players: HashMap<PlayerId, PlayerStatus>,
+    // 一匹ごと、理由ごとの点の合計
+    pub breakdown: HashMap<(PlayerId, ScoreReason), isize>,
+}
+
+impl Status {
+    pub fn get(&self, player: PlayerId) -> PlayerStatus {
+        self.players.get(&player).copied().unwrap_or_default()
+    }
+
+    // 一番スコアの高い一匹。門の出し方やステージの目標はこれを見る
+    pub fn best(&self) -> PlayerStatus {
+        self.players
+            .values()
+            .max_by_key(|status| (status.score, status.gates))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    // 点の付いた理由ごとの合計
+    pub fn breakdown(&self, player: PlayerId) -> Vec<(ScoreReason, isize)> {
+        ScoreReason::ALL
+            .iter()
+            .flat_map(|reason| {
+                self.breakdown
+                    .get(&(player, *reason))
+                    .map(|points| (*reason, *points))
+            })
+            .collect()
+    }
 }
 
 fn setup(commands: &mut Commands, asset_server: Res<AssetServer>) {
@@ -76,7 +137,191 @@ fn setup(commands: &mut Commands, asset_server: Res<AssetServer>) {
             },
             ..Default::default()
         })
-        .with(ScoreText);
+        .with(ScoreText)
+        .spawn(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(2.0),
+                    right: Val::Percent(70.0),
+                    top: Val::Percent(14.0),
+                    bottom: Val::Percent(86.0),
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: "".to_string(),
+                font: font.clone(),
+                style: TextStyle {
+                    font_size: GAUGE_SIZE,
+                    color: GAUGE_COLOR,
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        })
+        .with(GaugeText)
+        .spawn(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(2.0),
+                    right: Val::Percent(98.0),
+                    top: Val::Percent(95.0),
+                    bottom: Val::Percent(5.0),
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: LEVEL_PREFIX.to_string(),
+                font: font.clone(),
+                style: TextStyle {
+                    font_size: LEVEL_SIZE,
+                    color: LEVEL_COLOR,
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        })
+        .with(LevelText)
+        .spawn(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(2.0),
+                    right: Val::Percent(98.0),
+                    top: Val::Percent(10.0),
+                    bottom: Val::Percent(90.0),
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: "".to_string(),
+                font: font.clone(),
+                style: TextStyle {
+                    font_size: EDITOR_SIZE,
+                    color: EDITOR_COLOR,
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        })
+        .with(EditorText)
+        .spawn(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(2.0),
+                    right: Val::Percent(98.0),
+                    top: Val::Percent(91.0),
+                    bottom: Val::Percent(9.0),
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: MODE_PREFIX.to_string(),
+                font: font.clone(),
+                style: TextStyle {
+                    font_size: LEVEL_SIZE,
+                    color: LEVEL_COLOR,
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        })
+        .with(ModeText)
+        .spawn(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(35.0),
+                    right: Val::Percent(65.0),
+                    top: Val::Percent(30.0),
+                    bottom: Val::Percent(70.0),
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: "".to_string(),
+                font: font.clone(),
+                style: TextStyle {
+                    font_size: TALLY_SIZE,
+                    color: TALLY_COLOR,
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        })
+        .with(TallyText)
+        .spawn(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(35.0),
+                    right: Val::Percent(65.0),
+                    top: Val::Percent(14.0),
+                    bottom: Val::Percent(86.0),
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: "".to_string(),
+                font: font.clone(),
+                style: TextStyle {
+                    font_size: ROUND_SIZE,
+                    color: ROUND_COLOR,
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        })
+        .with(RoundText)
+        .spawn(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(30.0),
+                    right: Val::Percent(70.0),
+                    top: Val::Percent(40.0),
+                    bottom: Val::Percent(60.0),
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: "".to_string(),
+                font: font.clone(),
+                style: TextStyle {
+                    font_size: TITLE_SIZE,
+                    color: TITLE_COLOR,
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        })
+        .with(TitleText)
+        .spawn(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(40.0),
+                    right: Val::Percent(60.0),
+                    top: Val::Percent(22.0),
+                    bottom: Val::Percent(78.0),
+                },
+                ..Default::default()
+            },
+            text: Text {
+                value: "".to_string(),
+                font: font.clone(),
+                style: TextStyle {
+                    font_size: GHOST_SIZE,
+                    color: GHOST_BEHIND_COLOR,
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        })
+        .with(GhostText);
 }
 
 fn on_through_gate(
@@ -84,23 +329,34 @@ fn on_through_gate(
     mut status: ResMut<Status>,
     (events, mut reader): (Res<Events<ThroughGate>>, Local<EventReader<ThroughGate>>),
 ) {
-    if let Centipede::Alive(centipede) = &centipede_container.centipede {
-        for _ in reader.iter(&events) {
-            status.score +=
-                (centipede.tail_count as f32 * centipede.speed / 100.0).floor() as usize;
-            if status.score >= status.high_score {
-                status.high_score = status.score;
-            }
+    for event in reader.iter(&events) {
+        if centipede_container.alive(event.player).is_some() {
+            status.players.entry(event.player).or_default().gates += 1;
         }
     }
 }
 
+// 点はscoreの決まりが付けたものだけを足す。0より下にはならない
+fn on_score_awarded(
+    mut status: ResMut<Status>,
+    (events, mut reader): (Res<Events<ScoreAwarded>>, Local<EventReader<ScoreAwarded>>),
+) {
+    for event in reader.iter(&events) {
+        *status
+            .breakdown
+            .entry((event.player, event.reason))
+            .or_default() += event.points;
+        let player_status = status.players.entry(event.player).or_default();
+        player_status.score = (player_status.score as isize + event.points).max(0) as usize;
+    }
+}
+
 fn on_game_start(
     mut status: ResMut<Status>,
     (events, mut reader): (Res<Events<GameStart>>, Local<EventReader<GameStart>>),
 ) {
     for _ in reader.iter(&events) {
-        status.score = 0;
+        *status = Status::default();
     }
 }
 
@@ -111,7 +367,7 @@ fn fps_update_system(
     for mut text in fps_query.iter_mut() {
         if let Some(fps) = diagnostics.get(FrameTimeDiagnosticsPlugin::FPS) {
             if let Some(average) = fps.average() {
-                text.value = format!("{:} {:.2}", FPS_PREFIX, average).into();
+                text.value = format!("{:} {:.2}", FPS_PREFIX, average);
             }
         }
     }
@@ -120,22 +376,392 @@ fn fps_update_system(
 fn score_update_system(
     centipede_container: Res<CentipedeContainer>,
     status: Res<Status>,
+    session: Res<mode::Session>,
+    levels: Res<level::Levels>,
+    leaderboard: Res<leaderboard::Leaderboard>,
     mut score_query: Query<&mut Text, With<ScoreText>>,
 ) {
-    if let Centipede::Alive(centipede) = &centipede_container.centipede {
-        for mut text in score_query.iter_mut() {
-            text.value = format!(
-                "{:} {:.0}              {:} {:.0}              {:} {:.0}              {:} {:.0}",
+    // 遊び方とステージごとの最高記録
+    let high_score = leaderboard
+        .best(&leaderboard::BoardKey::current(&session, &levels))
+        .map_or(0, |record| record.score)
+        .max(status.best().score);
+
+    // 二人以上いるときは、誰の数字か分かるように番号を付けて一行ずつ
+    let multiplayer = centipede_container.players.len() > 1;
+    let lines: Vec<_> = centipede_container
+        .iter_alive()
+        .map(|centipede| {
+            format!(
+                "{:}{:} {:.0}              {:} {:.0}              {:} {:.0}              {:} {:.0}",
+                if multiplayer {
+                    format!("{:}{:}  ", PLAYER_PREFIX, centipede.player.0 + 1)
+                } else {
+                    "".to_string()
+                },
                 SPEED_PREFIX,
                 centipede.speed,
                 TAIL_PREFIX,
                 centipede.tail_count,
                 SCORE_PREFIX,
-                status.score,
+                status.get(centipede.player).score,
                 HIGH_SCORE_PREFIX,
-                status.high_score,
+                high_score,
+            )
+        })
+        .collect();
+    if lines.is_empty() {
+        return;
+    }
+
+    for mut text in score_query.iter_mut() {
+        text.value = lines.join("\n");
+    }
+}
+
+// ratio(0.0〜1.0)だけ埋まった目盛り
+fn gauge(ratio: f32) -> String {
+    let full = ((ratio.clamp(0.0, 1.0) * GAUGE_WIDTH as f32).round() as usize).min(GAUGE_WIDTH);
+    format!(
+        "[{:}{:}]",
+        GAUGE_FULL.to_string().repeat(full),
+        GAUGE_EMPTY.to_string().repeat(GAUGE_WIDTH - full)
+    )
+}
+
+// 速さと上限、次の支払いまでのブレーキ、ブーストが使えるまでの時間
+fn gauge_update_system(
+    time: Res<clock::Clock>,
+    centipede_container: Res<CentipedeContainer>,
+    model: Res<speed::SpeedModel>,
+    throttles: Res<speed::Throttles>,
+    mut gauge_query: Query<&mut Text, With<GaugeText>>,
+) {
+    let multiplayer = centipede_container.players.len() > 1;
+    let lines: Vec<_> = centipede_container
+        .iter_alive()
+        .map(|centipede| {
+            let throttle = throttles.get(centipede.player);
+            let boost = throttle.boost_ratio(time.seconds_since_startup());
+            format!(
+                "{:}{:} {:}    {:} {:}    {:} {:}",
+                if multiplayer {
+                    format!("{:}{:}  ", PLAYER_PREFIX, centipede.player.0 + 1)
+                } else {
+                    "".to_string()
+                },
+                SPEED_PREFIX,
+                gauge(centipede.speed / model.cap),
+                BRAKE_PREFIX,
+                gauge(throttle.brake_ratio()),
+                BOOST_PREFIX,
+                if boost >= 1.0 {
+                    GAUGE_READY.to_string()
+                } else {
+                    gauge(boost)
+                },
+            )
+        })
+        .collect();
+
+    for mut text in gauge_query.iter_mut() {
+        text.value = lines.join("\n");
+    }
+}
+
+// 選択中のステージと進み具合、選べるステージの一覧
+fn level_update_system(
+    levels: Res<level::Levels>,
+    progress: Res<level::LevelProgress>,
+    mut level_query: Query<&mut Text, With<LevelText>>,
+) {
+    let current = match levels.current() {
+        Some(level) => format!(
+            "{:} {:}    {:} {:}/{:}    {:} {:}{:}",
+            LEVEL_PREFIX,
+            level.name,
+            GOAL_PREFIX,
+            progress.gates,
+            level
+                .goal
+                .gates
+                .map_or_else(|| "-".to_string(), |gates| gates.to_string()),
+            PAR_PREFIX,
+            level.par_score,
+            if progress.cleared {
+                format!("    {:}", CLEAR_TEXT)
+            } else {
+                "".to_string()
+            },
+        ),
+        None => format!("{:} {:}", LEVEL_PREFIX, ENDLESS_LEVEL_NAME),
+    };
+
+    let list = std::iter::once(format!("0: {:}", ENDLESS_LEVEL_NAME))
+        .chain(
+            levels
+                .list
+                .iter()
+                .take(LEVEL_KEYS.len() - 1)
+                .enumerate()
+                .map(|(i, level)| format!("{:}: {:}", i + 1, level.name)),
+        )
+        .collect::<Vec<_>>()
+        .join("  ");
+
+    for mut text in level_query.iter_mut() {
+        text.value = format!("{:}        [{:}]", current, list);
+    }
+}
+
+// エディタの道具と操作方法
+fn editor_update_system(
+    editor: Res<editor::Editor>,
+    levels: Res<level::Levels>,
+    mut editor_query: Query<&mut Text, With<EditorText>>,
+) {
+    let value = match levels.current().filter(|_| editor.active) {
+        Some(level) => {
+            let detail = match editor.tool {
+                editor::Tool::Gate => match editor.selected.and_then(|i| level.gates.get(i)) {
+                    Some(gate) => format!(
+                        "WIDTH {:.0}  ROTATION {:.0}  [UP/DOWN] WIDTH  [LEFT/RIGHT] ROTATE  [DEL] DELETE",
+                        gate.width, gate.rotation
+                    ),
+                    None => "CLICK TO PLACE OR SELECT".to_string(),
+                },
+                editor::Tool::Obstacle => "CLICK: NONE > WALL > ASTEROID  DRAG TO PAINT".to_string(),
+                editor::Tool::Start => format!(
+                    "DIRECTION {:.0}  CLICK TO MOVE  [LEFT/RIGHT] ROTATE",
+                    level.start.direction
+                ),
+            };
+            format!(
+                "{:} {:?}    {:}    [G]ATE [O]BSTACLE [H]EAD  [S]AVE  [P]LAY",
+                EDITOR_PREFIX, editor.tool, detail
+            )
+        }
+        None => "".to_string(),
+    };
+
+    for mut text in editor_query.iter_mut() {
+        text.value = value.clone();
+    }
+}
+
+// 遊び方と残り時間
+fn mode_update_system(
+    time: Res<clock::Clock>,
+    centipede_container: Res<CentipedeContainer>,
+    session: Res<mode::Session>,
+    autopilot: Res<autopilot::Autopilot>,
+    mut mode_query: Query<&mut Text, With<ModeText>>,
+) {
+    let remaining = match (session.remaining(&time), centipede_container.playing()) {
+        (Some(remaining), true) => format!("    {:} {:.1}", TIME_PREFIX, remaining),
+        _ if session.mode == mode::GameMode::Zen => format!(
+            "    {:} [UP/DOWN]  [L]{:}",
+            SPEED_PREFIX,
+            if session.speed_pinned {
+                PINNED_TEXT
+            } else {
+                ""
+            }
+        ),
+        _ => "".to_string(),
+    };
+
+    // サバイバルでは次に尾が落ちるまでの時間を出し、迫ったら赤くする
+    let decay = Some(())
+        .filter(|_| centipede_container.playing())
+        .and_then(|_| session.decay(&time));
+    let decay_text = decay.map_or_else(String::new, |(next, interval)| {
+        format!("    {:} {:.1}/{:.1}", DECAY_PREFIX, next, interval)
+    });
+    // botがいれば強さ
+    let bot = if centipede_container
+        .players
+        .iter()
+        .any(|source| matches!(source, input::InputSource::Bot(_)))
+    {
+        format!(
+            "    {:} {:} [B][N]",
+            BOT_PREFIX,
+            autopilot.difficulty.name()
+        )
+    } else {
+        "".to_string()
+    };
+    let color = match decay {
+        Some((next, _)) if next <= SURVIVAL_WARNING_SECONDS => DECAY_WARNING_COLOR,
+        _ => LEVEL_COLOR,
+    };
+
+    for mut text in mode_query.iter_mut() {
+        text.value = format!(
+            "{:} {:} [M]{:}{:}{:}    {:} {:} [R]{:}",
+            MODE_PREFIX,
+            session.mode.name(),
+            remaining,
+            decay_text,
+            bot,
+            SEED_PREFIX,
+            session.seed,
+            if session.seed_locked { LOCKED_TEXT } else { "" }
+        );
+        text.style.color = color;
+    }
+}
+
+// タイムアタックが終わったあとの結果と上位の記録
+fn tally_update_system(
+    centipede_container: Res<CentipedeContainer>,
+    status: Res<Status>,
+    session: Res<mode::Session>,
+    levels: Res<level::Levels>,
+    leaderboard: Res<leaderboard::Leaderboard>,
+    mut tally_query: Query<&mut Text, With<TallyText>>,
+) {
+    let value = match (centipede_container.playing(), &leaderboard.last) {
+        (false, Some(last)) if last.cause == GameOverCause::TimeUp => {
+            let ranking = leaderboard
+                .records(&leaderboard::BoardKey::current(&session, &levels))
+                .iter()
+                .take(5)
+                .enumerate()
+                .map(|(i, record)| {
+                    format!(
+                        "{:}. {:} {:}  {:} {:}",
+                        i + 1,
+                        SCORE_PREFIX,
+                        record.score,
+                        GOAL_PREFIX,
+                        record.gates
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            // 点の内訳
+            let breakdown = status
+                .breakdown(last.player)
+                .iter()
+                .map(|(reason, points)| format!("{:} {:+}", reason.name(), points))
+                .collect::<Vec<_>>()
+                .join("  ");
+
+            format!(
+                "{:}\n{:} {:}\n{:}\n{:} {:}\n{:} {:}\n\n{:}",
+                TIME_UP_TEXT,
+                SCORE_PREFIX,
+                last.record.score,
+                breakdown,
+                GOAL_PREFIX,
+                last.record.gates,
+                RANK_PREFIX,
+                last.rank
+                    .map_or_else(|| "-".to_string(), |rank| format!("#{:}", rank)),
+                ranking
             )
-            .into();
+        }
+        _ => "".to_string(),
+    };
+
+    for mut text in tally_query.iter_mut() {
+        text.value = value.clone();
+    }
+}
+
+// 対戦で取ったラウンドの数と、直前のラウンドの結果
+fn round_update_system(
+    session: Res<mode::Session>,
+    centipede_container: Res<CentipedeContainer>,
+    versus: Res<versus::Match>,
+    mut round_query: Query<&mut Text, With<RoundText>>,
+) {
+    let value = if session.mode == mode::GameMode::Versus {
+        let wins = (0..centipede_container.players.len())
+            .map(|index| {
+                format!(
+                    "{:}{:} {:}",
+                    PLAYER_PREFIX,
+                    index + 1,
+                    versus.wins(PlayerId(index))
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(" - ");
+        let result = match (versus.decided, versus.last_winner, versus.champion()) {
+            (true, _, Some(champion)) => {
+                format!(
+                    "\n{:}{:} {:}",
+                    PLAYER_PREFIX,
+                    champion.0 + 1,
+                    MATCH_WON_TEXT
+                )
+            }
+            (true, Some(winner), None) => {
+                format!("\n{:}{:} {:}", PLAYER_PREFIX, winner.0 + 1, ROUND_WON_TEXT)
+            }
+            (true, None, None) => format!("\n{:}", DRAW_TEXT),
+            _ => "".to_string(),
+        };
+        format!(
+            "{:}  {:}  ({:} {:}){:}",
+            ROUND_PREFIX, wins, BEST_OF_PREFIX, VERSUS_ROUNDS, result
+        )
+    } else {
+        "".to_string()
+    };
+
+    for mut text in round_query.iter_mut() {
+        text.value = value.clone();
+    }
+}
+
+// デモ中のタイトルと、点滅する案内
+fn title_update_system(
+    time: Res<clock::Clock>,
+    attract: Res<title::Attract>,
+    mut title_query: Query<&mut Text, With<TitleText>>,
+) {
+    let value = if attract.active {
+        let blink =
+            (time.seconds_since_startup() / (PRESS_START_BLINK_SECONDS * 2.0)).fract() < 0.5;
+        format!(
+            "{:}\n\n{:}",
+            TITLE_TEXT,
+            if blink { PRESS_START_TEXT } else { "" }
+        )
+    } else {
+        "".to_string()
+    };
+
+    for mut text in title_query.iter_mut() {
+        text.value = value.clone();
+    }
+}
+
+// 門をくぐったときの幽霊との差。勝っていれば緑、負けていれば赤
+fn ghost_update_system(
+    time: Res<clock::Clock>,
+    ghosts: Res<ghost::Ghosts>,
+    mut ghost_query: Query<&mut Text, With<GhostText>>,
+) {
+    let delta = ghosts
+        .last_delta
+        .filter(|(_, at)| time.seconds_since_startup() - at < GHOST_DELTA_SECONDS);
+
+    for mut text in ghost_query.iter_mut() {
+        match delta {
+            Some((delta, _)) => {
+                text.value = format!("{:} {:+.2}", GHOST_PREFIX, delta);
+                text.style.color = if delta > 0.0 {
+                    GHOST_AHEAD_COLOR
+                } else {
+                    GHOST_BEHIND_COLOR
+                };
+            }
+            None => text.value = "".to_string(),
         }
     }
 }
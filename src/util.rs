@@ -1,38 +1,123 @@
 use crate::*;
+use serde::{Deserialize, Serialize};
 // 各pluginで共通して使用するstruct等
 
+// プレイヤーの番号。0が一人目
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct PlayerId(pub usize);
+
 // プレイヤー・キャラクター。死んでるときがあるので、enumを持つだけ
+// 一つの盤面に何匹でもいられるように、PlayerIdの番号順に並べる
 pub struct CentipedeContainer {
-    pub centipede: Centipede,
+    pub centipedes: Vec<Centipede>,
+    // 次のゲームに参加するプレイヤーの入力元。PlayerIdの番号順
+    pub players: Vec<input::InputSource>,
 }
 
 impl Default for CentipedeContainer {
     fn default() -> Self {
         Self {
-            centipede: Centipede::Dead(0.0),
+            centipedes: vec![],
+            players: vec![input::InputSource::Mouse],
         }
     }
 }
 
 impl CentipedeContainer {
-    pub fn alive(&self) -> Option<&Alive> {
-        match &self.centipede {
+    pub fn alive(&self, player: PlayerId) -> Option<&Alive> {
+        match self.centipedes.get(player.0)? {
             Centipede::Alive(alive) => Some(alive),
             _ => None,
         }
     }
 
-    pub fn alive_mut(&mut self) -> Option<&mut Alive> {
-        match &mut self.centipede {
+    pub fn alive_mut(&mut self, player: PlayerId) -> Option<&mut Alive> {
+        match self.centipedes.get_mut(player.0)? {
             Centipede::Alive(alive) => Some(alive),
             _ => None,
         }
     }
 
-    pub fn head_entity(&self) -> Option<Entity> {
-        match &self.centipede {
-            Centipede::Alive(alive) => Some(alive.head_entity),
-            _ => None,
+    pub fn iter_alive(&self) -> impl Iterator<Item = &Alive> {
+        self.centipedes
+            .iter()
+            .flat_map(|centipede| match centipede {
+                Centipede::Alive(alive) => Some(alive),
+                _ => None,
+            })
+    }
+
+    pub fn iter_alive_mut(&mut self) -> impl Iterator<Item = &mut Alive> {
+        self.centipedes
+            .iter_mut()
+            .flat_map(|centipede| match centipede {
+                Centipede::Alive(alive) => Some(alive),
+                _ => None,
+            })
+    }
+
+    // 誰か一匹でも生きていればゲーム中
+    pub fn playing(&self) -> bool {
+        self.iter_alive().next().is_some()
+    }
+
+    pub fn head_entity(&self, player: PlayerId) -> Option<Entity> {
+        self.alive(player).map(|alive| alive.head_entity)
+    }
+
+    pub fn head_entities(&self) -> Vec<Entity> {
+        self.iter_alive().map(|alive| alive.head_entity).collect()
+    }
+
+    // 全員が死んでいれば、最後に死んだ時刻
+    pub fn dead_at(&self) -> Option<f64> {
+        self.centipedes
+            .iter()
+            .try_fold(0.0, |latest: f64, centipede| match centipede {
+                Centipede::Dead(dead_at) => Some(latest.max(*dead_at)),
+                Centipede::Alive(_) => None,
+            })
+    }
+
+    // 一匹だけ死なせる
+    pub fn kill(
+        &mut self,
+        player: PlayerId,
+        dead_at: f64,
+        cause: GameOverCause,
+        game_over_events: &mut Events<GameOver>,
+    ) {
+        if let Some(centipede) = self.alive(player) {
+            game_over_events.send(GameOver {
+                player,
+                head_entity: centipede.head_entity,
+                cause,
+                position: centipede.head(),
+                head: HeadFacts::new(centipede, centipede.tail_count),
+            });
+            self.centipedes[player.0] = Centipede::Dead(dead_at);
+        }
+    }
+
+    // 生きていれば全員のゲームを終わらせる。dead_atが0.0ならすぐに始め直す
+    pub fn finish(
+        &mut self,
+        dead_at: f64,
+        cause: GameOverCause,
+        game_over_events: &mut Events<GameOver>,
+    ) {
+        for index in 0..self.centipedes.len() {
+            self.kill(PlayerId(index), dead_at, cause, game_over_events);
+        }
+        if dead_at == 0.0 {
+            self.restart();
+        }
+    }
+
+    // 全員死んだ直後扱いにして、すぐに始める
+    pub fn restart(&mut self) {
+        for centipede in self.centipedes.iter_mut() {
+            *centipede = Centipede::Dead(0.0);
         }
     }
 }
@@ -45,6 +130,8 @@ pub enum Centipede {
 
 // 生きてる場合
 pub struct Alive {
+    pub player: PlayerId,
+    pub input: input::InputSource,
     pub head_entity: Entity,
     pub speed: f32,
     pub movement: Movement,
@@ -54,26 +141,60 @@ pub struct Alive {
 }
 
 impl Alive {
-    pub fn default(head_entity: Entity) -> Self {
+    // positionからdirectionの向きに出発する。尾は後ろにまっすぐ伸ばしておく
+    pub fn new(
+        player: PlayerId,
+        input: input::InputSource,
+        head_entity: Entity,
+        position: Position,
+        direction: Vec2,
+    ) -> Self {
+        let mut behind = position;
+        behind.move_to_with_distance(-direction, 1000.0);
         Self {
+            player,
+            input,
             head_entity,
             speed: DEFAULT_SPEED,
-            movement: Movement::Linear(Vec2 { x: 1.0, y: 0.0 }),
-            last_move: Vec2 { x: 1.0, y: 0.0 },
+            movement: Movement::Linear(direction),
+            last_move: direction,
             tail_count: INITIAL_CENTIPEDE_LENGTH,
-            position_history: vec![
-                Position {
-                    x: -1000.0,
-                    y: 0.0,
-                    visible: true,
-                },
-                Position {
-                    x: 0.0,
-                    y: 0.0,
-                    visible: true,
-                },
-            ],
+            position_history: vec![behind, position],
+        }
+    }
+
+    // 尾を並べるのに要る分だけ跡を残し、それより古い跡は捨てる
+    pub fn trim_history(&mut self) {
+        let needed = (self.tail_count + POSITION_HISTORY_SPARE_TAILS) as f32 * TAIL_DISTANCE;
+        let mut distance = 0.0;
+        let mut keep_from = 0;
+        for (index, pair) in self.position_history.windows(2).enumerate().rev() {
+            distance += pair[0].distance(&pair[1]);
+            if distance > needed {
+                keep_from = index;
+                break;
+            }
         }
+        self.position_history.drain(..keep_from);
+    }
+
+    // 今の頭の位置
+    pub fn head(&self) -> Position {
+        *self
+            .position_history
+            .last()
+            .expect("history is never empty")
+    }
+
+    // この刻みで頭が動いた始まりと終わり
+    pub fn last_step(&self) -> (Position, Position) {
+        let to = self.head();
+        let from = Position {
+            x: to.x - self.last_move.x,
+            y: to.y - self.last_move.y,
+            visible: to.visible,
+        };
+        (from, to)
     }
 }
 
@@ -82,16 +203,42 @@ impl Alive {
 pub enum Movement {
     // 中心地点
     Circular(CircularMove),
+    // 回りながら半径を変える。渦巻き
+    Spiral(SpiralMove),
     // ベクトル
     Linear(Vec2),
 }
 
+impl Movement {
+    // 回っていれば、その中心と向き
+    pub fn orbit(&self) -> Option<CircularMove> {
+        match self {
+            Movement::Circular(circular_move) => Some(*circular_move),
+            Movement::Spiral(SpiralMove {
+                center, clockwise, ..
+            }) => Some(CircularMove {
+                center: *center,
+                clockwise: *clockwise,
+            }),
+            Movement::Linear(_) => None,
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
 pub struct CircularMove {
     pub center: Position,
     pub clockwise: bool,
 }
 
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SpiralMove {
+    pub center: Position,
+    pub clockwise: bool,
+    // 一秒で半径が変わる量。負なら締める
+    pub rate: f32,
+}
+
 // X: 左右(右が大きい)、Y:上下(上が大きい)、中心が0の座標
 #[derive(Default, Copy, Clone, PartialEq, Debug)]
 pub struct Position {
@@ -155,16 +302,115 @@ impl From<Vec3> for Position {
     }
 }
 
-// http://www5d.biglobe.ne.jp/~tomoya03/shtml/algorithm/Intersection.htm
-// 線分が交差するかの判定
-pub fn intersection(a1: &Position, a2: &Position, b1: &Position, b2: &Position) -> bool {
-    intersect(a1, a2, b1, b2) && intersect(b1, b2, a1, a2)
+// 盤面のパネルの位置。左下のパネルが0, 0
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct Panel {
+    pub x: isize,
+    pub y: isize,
 }
 
-fn intersect(x1: &Position, x2: &Position, y1: &Position, y2: &Position) -> bool {
-    ((x1.x - x2.x) * (y1.y - x1.y) + (x1.y - x2.y) * (x1.x - y1.x))
-        * ((x1.x - x2.x) * (y2.y - x1.y) + (x1.y - x2.y) * (x1.x - y2.x))
-        < 0.0
+// 盤面の大きさ。パネルの数で持ち、中心が0, 0
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Board {
+    pub x: isize,
+    pub y: isize,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self {
+            x: PANEL_X_COUNT,
+            y: PANEL_Y_COUNT,
+        }
+    }
+}
+
+impl Board {
+    pub fn x_size(&self) -> f32 {
+        self.x as f32 * PANEL_SIZE
+    }
+
+    pub fn y_size(&self) -> f32 {
+        self.y as f32 * PANEL_SIZE
+    }
+
+    pub fn x_border(&self) -> f32 {
+        self.x_size() / 2.0
+    }
+
+    pub fn y_border(&self) -> f32 {
+        self.y_size() / 2.0
+    }
+
+    pub fn contains(&self, panel: &Panel) -> bool {
+        (0..self.x).contains(&panel.x) && (0..self.y).contains(&panel.y)
+    }
+
+    // 座標が乗っているパネルの升目。盤面の外でも同じ並びで数える
+    pub fn cell(&self, position: &Position) -> Panel {
+        Panel {
+            x: ((position.x + self.x_border()) / PANEL_SIZE).floor() as isize,
+            y: ((position.y + self.y_border()) / PANEL_SIZE).floor() as isize,
+        }
+    }
+
+    // 座標が乗っているパネル。盤面の外ならNone
+    pub fn panel(&self, position: &Position) -> Option<Panel> {
+        let panel = self.cell(position);
+        if self.contains(&panel) {
+            Some(panel)
+        } else {
+            None
+        }
+    }
+
+    // パネルの中心の座標
+    pub fn center(&self, panel: &Panel) -> Position {
+        Position {
+            x: panel.x as f32 * PANEL_SIZE - self.x_border() + PANEL_SIZE / 2.0,
+            y: panel.y as f32 * PANEL_SIZE - self.y_border() + PANEL_SIZE / 2.0,
+            visible: true,
+        }
+    }
+
+    // 盤面の中のランダムなパネル
+    pub fn random_panel(&self, rng: &mut impl rand::Rng) -> Panel {
+        Panel {
+            x: (rng.gen::<f32>() * self.x as f32) as isize,
+            y: (rng.gen::<f32>() * self.y as f32) as isize,
+        }
+    }
+}
+
+// 折れ線グラフ。(x, y)をxの昇順で並べ、範囲外は端の値のまま
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Curve(pub Vec<(f32, f32)>);
+
+impl Curve {
+    pub fn constant(y: f32) -> Self {
+        Curve(vec![(0.0, y)])
+    }
+
+    pub fn sample(&self, x: f32) -> f32 {
+        let points = &self.0;
+        match (points.first(), points.last()) {
+            (Some(first), _) if x <= first.0 => first.1,
+            (_, Some(last)) if x >= last.0 => last.1,
+            (None, _) | (_, None) => 0.0,
+            _ => points
+                .windows(2)
+                .find(|pair| x <= pair[1].0)
+                .map(|pair| {
+                    let ((x1, y1), (x2, y2)) = (pair[0], pair[1]);
+                    if x2 <= x1 {
+                        y2
+                    } else {
+                        y1 + (y2 - y1) * (x - x1) / (x2 - x1)
+                    }
+                })
+                .unwrap_or(0.0),
+        }
+    }
 }
 
 // 子要素を持つだけのコンテナバンドル
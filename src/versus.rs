@@ -0,0 +1,98 @@
+use crate::*;
+use std::collections::HashMap;
+
+pub struct ModPlugin;
+
+// 対戦。最後まで尾が残った一匹がラウンドを取り、先に規定数取った方の勝ち
+impl Plugin for ModPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Match>()
+            .add_system_to_stage(stage::POST_UPDATE, round_system.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_game_start.system())
+            .add_system_to_stage(stage::RECEIVE_EVENT, on_game_over.system());
+    }
+}
+
+// 取ったラウンドの数と、直前のラウンドの結果
+#[derive(Default)]
+pub struct Match {
+    pub wins: HashMap<PlayerId, usize>,
+    // 今のラウンドの勝敗が決まったか
+    pub decided: bool,
+    // 直前のラウンドを取った一匹。引き分けならNone
+    pub last_winner: Option<PlayerId>,
+}
+
+impl Match {
+    pub fn wins(&self, player: PlayerId) -> usize {
+        self.wins.get(&player).copied().unwrap_or(0)
+    }
+
+    // 過半数のラウンドを取った一匹
+    pub fn champion(&self) -> Option<PlayerId> {
+        self.wins
+            .iter()
+            .find(|(_, wins)| **wins > VERSUS_ROUNDS / 2)
+            .map(|(player, _)| *player)
+    }
+}
+
+// 残りが一匹になったらその一匹の勝ち。同時に全員いなくなったら引き分け
+fn round_system(
+    time: Res<clock::Clock>,
+    session: Res<mode::Session>,
+    mut versus: ResMut<Match>,
+    mut centipede_container: ResMut<CentipedeContainer>,
+    mut game_over_events: ResMut<Events<GameOver>>,
+) {
+    if session.mode != mode::GameMode::Versus
+        || versus.decided
+        || centipede_container.centipedes.len() < 2
+    {
+        return;
+    }
+
+    let alive: Vec<_> = centipede_container
+        .iter_alive()
+        .map(|centipede| centipede.player)
+        .collect();
+    if alive.len() > 1 {
+        return;
+    }
+
+    versus.decided = true;
+    versus.last_winner = alive.first().copied();
+    if let Some(winner) = versus.last_winner {
+        *versus.wins.entry(winner).or_default() += 1;
+        centipede_container.finish(
+            time.seconds_since_startup(),
+            GameOverCause::Won,
+            &mut game_over_events,
+        );
+    }
+}
+
+// 勝負が付いていたら、次のラウンドから新しい試合
+fn on_game_start(
+    mut versus: ResMut<Match>,
+    (events, mut reader): (Res<Events<GameStart>>, Local<EventReader<GameStart>>),
+) {
+    for _ in reader.iter(&events) {
+        if versus.champion().is_some() {
+            *versus = Match::default();
+        }
+        versus.decided = false;
+    }
+}
+
+// 遊び方やステージ、参加者が変わったら試合をやり直す
+fn on_game_over(
+    mut versus: ResMut<Match>,
+    (events, mut reader): (Res<Events<GameOver>>, Local<EventReader<GameOver>>),
+) {
+    for event in reader.iter(&events) {
+        if event.cause == GameOverCause::Aborted {
+            *versus = Match::default();
+        }
+    }
+}
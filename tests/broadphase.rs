@@ -0,0 +1,60 @@
+// 升目で絞り込んでも、本当にreach以内に入るものを取りこぼさないことを確かめる
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use space_centipede::{broadphase::Grid, *};
+
+const CASES: usize = 500;
+
+fn point(rng: &mut StdRng) -> Position {
+    // 盤面の外にはみ出したものも入る
+    Position {
+        x: rng.gen_range(-1000.0, 1000.0),
+        y: rng.gen_range(-600.0, 600.0),
+        visible: true,
+    }
+}
+
+#[test]
+fn query_returns_everything_within_reach() {
+    let mut rng = StdRng::seed_from_u64(43);
+    for _ in 0..CASES {
+        let mut grid = Grid::default();
+        let items: Vec<(usize, Position, Position)> = (0..50)
+            .map(|index| {
+                let from = point(&mut rng);
+                let mut to = from;
+                if rng.gen_bool(0.5) {
+                    to.x += rng.gen_range(-200.0, 200.0);
+                    to.y += rng.gen_range(-200.0, 200.0);
+                }
+                (index, from, to)
+            })
+            .collect();
+        for (index, from, to) in items.iter() {
+            grid.insert(from, to, *index);
+        }
+
+        let from = point(&mut rng);
+        let to = Position {
+            x: from.x + rng.gen_range(-300.0, 300.0),
+            y: from.y + rng.gen_range(-300.0, 300.0),
+            visible: true,
+        };
+        let reach = rng.gen_range(0.0, 120.0);
+        let found = grid.query(&from, &to, reach);
+
+        let mut sorted = found.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(sorted.len(), found.len(), "an item came back twice");
+
+        for (index, item_from, item_to) in items.iter() {
+            let hit = geometry::sweep_pair(&from, &to, item_from, item_to, reach).is_some()
+                || geometry::sweep_pair(&from, &from, item_from, item_from, reach).is_some()
+                || geometry::sweep(item_from, item_to, &from, reach).is_some()
+                || geometry::sweep(&from, &to, item_to, reach).is_some();
+            if hit {
+                assert!(found.contains(index), "missed {}", index);
+            }
+        }
+    }
+}
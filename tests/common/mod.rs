@@ -0,0 +1,66 @@
+// 窓も描画も無いアプリを組み立てて、時計をSTEPずつ進める
+// 一度updateしたら、そのappは別の場所へmoveしない(systemのCommandsがWorldの場所を覚えている)
+use bevy::{
+    asset::AssetPlugin,
+    core::DefaultTaskPoolOptions,
+    diagnostic::DiagnosticsPlugin,
+    input::InputPlugin,
+    text::{Font, FontLoader},
+    transform::TransformPlugin,
+    window::WindowPlugin,
+};
+use space_centipede::*;
+
+pub fn headless_app() -> App {
+    let mut builder = App::build();
+    builder
+        // 刻みが細かいので、threadを分けるより一つで回した方が速い
+        .add_resource(DefaultTaskPoolOptions::with_num_threads(1))
+        .add_plugins(MinimalPlugins)
+        .add_plugin(TransformPlugin)
+        .add_plugin(DiagnosticsPlugin)
+        .add_plugin(InputPlugin)
+        .add_plugin(WindowPlugin {
+            add_primary_window: false,
+            ..Default::default()
+        })
+        .add_plugin(AssetPlugin)
+        // 描画はしないので、組み立てに要る資源の置き場だけ用意する
+        .add_asset::<Mesh>()
+        .add_asset::<StandardMaterial>()
+        .add_asset::<ColorMaterial>()
+        .add_asset::<Font>()
+        .init_asset_loader::<FontLoader>()
+        .add_plugins(GamePlugins)
+        .add_resource(clock::Clock::fixed(STEP));
+    builder.app
+}
+
+// 一回の刻み(秒)
+pub const STEP: f64 = 1.0 / 60.0;
+
+// タイトルを閉じ、マウスで動かす一匹でlevelを始める
+#[allow(dead_code)]
+pub fn start(app: &mut App, level: level::Level) {
+    start_with(app, level, vec![input::InputSource::Mouse]);
+}
+
+// タイトルを閉じ、playersの参加者でlevelを始める
+// 盤面はステージを選んだときと同じくlevelのものにする
+#[allow(dead_code)]
+pub fn start_with(app: &mut App, level: level::Level, players: Vec<input::InputSource>) {
+    let board = level.board;
+    {
+        let mut levels = app.resources.get_mut::<level::Levels>().unwrap();
+        levels.list = vec![level];
+        levels.selected = Some(0);
+    }
+    *app.resources.get_mut::<Board>().unwrap() = board;
+    app.resources.get_mut::<title::Attract>().unwrap().active = false;
+    app.resources
+        .get_mut::<CentipedeContainer>()
+        .unwrap()
+        .players = players;
+    app.update();
+    assert_eq!(*app.resources.get::<Board>().unwrap(), board);
+}
@@ -0,0 +1,253 @@
+// 乱数で作った多数の図形で、接する、重なる、長さ0の場合も含めて当たり判定の性質を確かめる
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use space_centipede::*;
+
+const CASES: usize = 2000;
+// 誤差で答えが揺れる境目からこれだけ離れた場合だけを比べる
+const MARGIN: f32 = 0.01;
+
+fn rng() -> StdRng {
+    StdRng::seed_from_u64(41)
+}
+
+fn point(rng: &mut StdRng) -> Position {
+    Position {
+        x: rng.gen_range(-1000.0, 1000.0),
+        y: rng.gen_range(-1000.0, 1000.0),
+        visible: true,
+    }
+}
+
+// 長さがある程度ある線分
+fn segment(rng: &mut StdRng) -> (Position, Position) {
+    loop {
+        let (a, b) = (point(rng), point(rng));
+        if a.distance(&b) > 10.0 {
+            return (a, b);
+        }
+    }
+}
+
+fn lerp(a: &Position, b: &Position, ratio: f32) -> Position {
+    Position {
+        x: a.x + (b.x - a.x) * ratio,
+        y: a.y + (b.y - a.y) * ratio,
+        visible: true,
+    }
+}
+
+// a-bの向きに対して左へdistanceずらした点
+fn shift(point: &Position, a: &Position, b: &Position, distance: f32) -> Position {
+    let length = a.distance(b);
+    Position {
+        x: point.x - (b.y - a.y) / length * distance,
+        y: point.y + (b.x - a.x) / length * distance,
+        visible: true,
+    }
+}
+
+fn crosses(from: &Position, to: &Position, a: &Position, b: &Position) -> bool {
+    geometry::crossing(from, to, a, b).is_some()
+}
+
+#[test]
+fn crossing_does_not_depend_on_the_direction() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let (a1, a2, b1, b2) = (
+            point(&mut rng),
+            point(&mut rng),
+            point(&mut rng),
+            point(&mut rng),
+        );
+        let expected = crosses(&a1, &a2, &b1, &b2);
+        assert_eq!(crosses(&a2, &a1, &b1, &b2), expected);
+        assert_eq!(crosses(&a2, &a1, &b2, &b1), expected);
+    }
+}
+
+#[test]
+fn a_proper_crossing_passes() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let (b1, b2) = segment(&mut rng);
+        let center = lerp(&b1, &b2, rng.gen_range(0.05, 0.95));
+        let from = shift(&center, &b1, &b2, -rng.gen_range(1.0, 100.0));
+        let to = shift(&center, &b1, &b2, rng.gen_range(1.0, 100.0));
+
+        assert!(crosses(&from, &to, &b1, &b2));
+        assert!(crosses(&to, &from, &b1, &b2));
+    }
+}
+
+#[test]
+fn touching_at_an_endpoint_does_not_pass() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let (b1, b2) = segment(&mut rng);
+        let from = shift(&b1, &b1, &b2, -rng.gen_range(1.0, 100.0));
+        let to = shift(&b1, &b1, &b2, rng.gen_range(1.0, 100.0));
+
+        // 端で止まる、端から出る、端をまっすぐ通り抜ける
+        assert!(!crosses(&from, &b1, &b1, &b2));
+        assert!(!crosses(&b1, &to, &b1, &b2));
+        assert!(!crosses(&from, &to, &b1, &b2));
+    }
+}
+
+#[test]
+fn moving_along_the_line_does_not_pass() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let (b1, b2) = segment(&mut rng);
+        let inside = lerp(&b1, &b2, rng.gen_range(0.1, 0.9));
+        let beyond = lerp(&b1, &b2, rng.gen_range(1.1, 2.0));
+        let further = lerp(&b1, &b2, rng.gen_range(2.1, 3.0));
+
+        // 線に沿って動いてもくぐったことにはならない
+        assert!(!crosses(&inside, &beyond, &b1, &b2));
+        assert!(!crosses(&beyond, &further, &b1, &b2));
+        assert!(geometry::distance_to_segment(&inside, &b1, &b2) <= MARGIN);
+        assert!(geometry::distance_to_segment(&beyond, &b1, &b2) > MARGIN);
+    }
+}
+
+#[test]
+fn zero_length_segments_are_points() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let (b1, b2) = segment(&mut rng);
+        let on = lerp(&b1, &b2, rng.gen_range(0.0, 1.0));
+        let off = shift(&on, &b1, &b2, rng.gen_range(1.0, 100.0));
+
+        assert!(geometry::distance_to_segment(&on, &b1, &b2) <= MARGIN);
+        assert!(geometry::distance_to_segment(&off, &b1, &b2) > MARGIN);
+        assert!(!crosses(&on, &on, &b1, &b2));
+        assert!(!crosses(&off, &on, &b1, &b1));
+        assert_eq!(geometry::signed_distance(&on, &b1, &b1), None);
+        assert_eq!(geometry::closest_point(&off, &b1, &b1), b1);
+    }
+}
+
+// 一度だけ横切る道のりは、どこで区切っても、区切りがちょうど線の上に来ても一度だけ数える
+#[test]
+fn a_crossing_is_counted_once_however_the_path_is_sampled() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let (b1, b2) = segment(&mut rng);
+        let center = lerp(&b1, &b2, rng.gen_range(0.1, 0.9));
+        let start = shift(&center, &b1, &b2, -rng.gen_range(1.0, 100.0));
+        let end = shift(&center, &b1, &b2, rng.gen_range(1.0, 100.0));
+
+        let mut ratios: Vec<f32> = (0..rng.gen_range(0, 5))
+            .map(|_| rng.gen_range(0.0, 1.0))
+            .collect();
+        ratios.push(0.0);
+        ratios.push(1.0);
+        ratios.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mut samples: Vec<Position> = ratios
+            .iter()
+            .map(|ratio| lerp(&start, &end, *ratio))
+            .collect();
+        // ちょうど線の上で止まるフレーム
+        let on_line = samples.len() / 2;
+        samples.insert(on_line, center);
+        samples.insert(on_line, center);
+        samples.sort_by(|a, b| a.distance(&start).partial_cmp(&b.distance(&start)).unwrap());
+
+        let count = samples
+            .windows(2)
+            .filter(|pair| crosses(&pair[0], &pair[1], &b1, &b2))
+            .count();
+        assert_eq!(count, 1, "{:?}", samples);
+    }
+}
+
+#[test]
+fn closest_point_is_on_the_segment_and_closest() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let (a, b) = segment(&mut rng);
+        let target = point(&mut rng);
+        let closest = geometry::closest_point(&target, &a, &b);
+        let distance = geometry::distance_to_segment(&target, &a, &b);
+
+        assert!(geometry::distance_to_segment(&closest, &a, &b) <= MARGIN);
+        assert!(distance <= target.distance(&a) + MARGIN);
+        assert!(distance <= target.distance(&b) + MARGIN);
+        let somewhere = lerp(&a, &b, rng.gen_range(0.0, 1.0));
+        assert!(distance <= target.distance(&somewhere) + MARGIN);
+    }
+}
+
+#[test]
+fn sweep_finds_the_first_contact() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let (from, to) = segment(&mut rng);
+        let center = point(&mut rng);
+        let reach = rng.gen_range(1.0, 300.0);
+        let distance = geometry::distance_to_segment(&center, &from, &to);
+        if (distance - reach).abs() < MARGIN {
+            continue;
+        }
+
+        match geometry::sweep(&from, &to, &center, reach) {
+            Some(ratio) => {
+                assert!(distance <= reach);
+                let contact = lerp(&from, &to, ratio);
+                assert!((contact.distance(&center) - reach).abs() < MARGIN || ratio == 0.0);
+                // それより前には触れていない
+                if ratio > 0.0 {
+                    let before = lerp(&from, &to, ratio * 0.99);
+                    assert!(before.distance(&center) > reach - MARGIN);
+                }
+            }
+            None => assert!(distance > reach),
+        }
+    }
+}
+
+// 柱の真ん中を通り抜ける一歩でも、途中で柱に触れたことがわかる
+#[test]
+fn sweep_catches_a_step_through_a_center() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let center = point(&mut rng);
+        let (a, b) = segment(&mut rng);
+        let reach = rng.gen_range(1.0, 50.0);
+        let from = shift(&center, &a, &b, -rng.gen_range(reach * 2.0, 1000.0));
+        let to = shift(&center, &a, &b, rng.gen_range(reach * 2.0, 1000.0));
+
+        assert!(!geometry::touching(&from, &center, reach));
+        assert!(!geometry::touching(&to, &center, reach));
+        assert!(geometry::sweep(&from, &to, &center, reach).is_some());
+        // 止まっている点は、触れているかどうかと同じ
+        assert_eq!(geometry::sweep(&center, &center, &center, reach), Some(0.0));
+        assert_eq!(geometry::sweep(&from, &from, &center, reach), None);
+    }
+}
+
+// すれ違う二つの点は、どちらの刻みの両端でも離れていても、途中で触れたことがわかる
+#[test]
+fn sweep_pair_catches_two_points_passing_each_other() {
+    let mut rng = rng();
+    for _ in 0..CASES {
+        let meeting = point(&mut rng);
+        let (a, b) = segment(&mut rng);
+        let reach = rng.gen_range(1.0, 50.0);
+        let far = rng.gen_range(reach * 2.0, 500.0);
+        let from = shift(&meeting, &a, &b, -far);
+        let to = shift(&meeting, &a, &b, far);
+
+        assert!(geometry::sweep_pair(&from, &to, &to, &from, reach).is_some());
+        // 同じように動くものとは、近づかない限り触れない
+        assert_eq!(geometry::sweep_pair(&from, &to, &a, &b, 0.0), None);
+        let beside = shift(&from, &from, &to, reach * 2.0);
+        let beside_to = shift(&to, &from, &to, reach * 2.0);
+        assert_eq!(
+            geometry::sweep_pair(&from, &to, &beside, &beside_to, reach),
+            None
+        );
+    }
+}
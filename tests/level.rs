@@ -0,0 +1,43 @@
+// ステージファイルの読み込みで、遊べないステージが落ちずに弾かれることを確かめる
+use space_centipede::level::parse_level;
+
+#[test]
+fn spawns_are_sorted_by_time() {
+    let level = parse_level(
+        r#"(
+            name: "sorted",
+            spawns: [
+                (at: 15.0, gate: (x: 0.0, y: 0.0, width: 140.0, rotation: 0.0)),
+                (at: 10.0, gate: (x: 100.0, y: 0.0, width: 140.0, rotation: 0.0)),
+            ],
+        )"#,
+        "sorted",
+    )
+    .unwrap();
+    let times: Vec<_> = level.spawns.iter().map(|spawn| spawn.at).collect();
+    assert_eq!(times, vec![10.0, 15.0]);
+}
+
+#[test]
+fn a_spawn_time_that_is_not_a_number_is_rejected() {
+    let level = parse_level(
+        r#"(
+            name: "nan",
+            spawns: [
+                (at: 10.0, gate: (x: 0.0, y: 0.0, width: 140.0, rotation: 0.0)),
+                (at: NaN, gate: (x: 100.0, y: 0.0, width: 140.0, rotation: 0.0)),
+            ],
+        )"#,
+        "nan",
+    );
+    assert!(level.is_none());
+}
+
+#[test]
+fn boards_too_small_to_play_are_rejected() {
+    for board in &["(x: 0, y: 11)", "(x: 17, y: -3)", "(x: 2, y: 2)"] {
+        let source = format!(r#"(name: "small", board: {})"#, board);
+        assert!(parse_level(&source, "small").is_none(), "{}", board);
+    }
+    assert!(parse_level(r#"(name: "ok", board: (x: 5, y: 5))"#, "ok").is_some());
+}
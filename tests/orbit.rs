@@ -0,0 +1,168 @@
+// 頭の上や遠くを押して回り、半径が下限と上限の間に落ち着くことを確かめる
+mod common;
+
+use space_centipede::*;
+
+// 半径が落ち着くまで待つ刻み
+const TICKS: usize = 600;
+// 落ち着いた半径と決まりとの差の許し
+const TOLERANCE: f32 = 0.5;
+
+// 上限の半径の円が盤面に収まるように、広い盤面で始める
+fn play(app: &mut App) {
+    common::start(
+        app,
+        level::Level::new("orbit".to_string(), Board { x: 60, y: 40 }),
+    );
+}
+
+fn head(app: &App) -> Position {
+    *app.resources
+        .get::<CentipedeContainer>()
+        .unwrap()
+        .alive(PlayerId(0))
+        .unwrap()
+        .position_history
+        .last()
+        .unwrap()
+}
+
+// spiralが正なら締め、負なら緩めながら押す
+fn press(app: &mut App, position: Position, spiral: f32) {
+    app.resources.get_mut::<input::Controls>().unwrap().set(
+        input::InputSource::Mouse,
+        input::Control {
+            position,
+            pressed: true,
+            spiral,
+            ..Default::default()
+        },
+    );
+}
+
+// 押し続けてticksだけ回り、最後の中心と半径。壁で跳ね返らずに回り続ける
+fn orbit_for(app: &mut App, ticks: usize) -> (Position, f32) {
+    let board = *app.resources.get::<Board>().unwrap();
+    for _ in 0..ticks {
+        app.update();
+        let head = head(app);
+        assert!(head.x.is_finite() && head.y.is_finite(), "{:?}", head);
+        assert!(
+            head.x.abs() < board.x_border() && head.y.abs() < board.y_border(),
+            "{:?}",
+            head
+        );
+    }
+    let centipede_container = app.resources.get::<CentipedeContainer>().unwrap();
+    let centipede = centipede_container.alive(PlayerId(0)).unwrap();
+    match centipede.movement.orbit() {
+        Some(CircularMove { center, .. }) => {
+            let head = centipede.position_history.last().unwrap();
+            (center, head.distance(&center))
+        }
+        None => panic!("not orbiting: {:?}", centipede.movement),
+    }
+}
+
+fn orbit(app: &mut App) -> (Position, f32) {
+    orbit_for(app, TICKS)
+}
+
+fn movement(app: &App) -> Movement {
+    app.resources
+        .get::<CentipedeContainer>()
+        .unwrap()
+        .alive(PlayerId(0))
+        .unwrap()
+        .movement
+}
+
+#[test]
+fn pressing_on_the_head_orbits_at_the_minimum_radius() {
+    let mut app = common::headless_app();
+    play(&mut app);
+    let head = head(&app);
+    press(&mut app, head, 0.0);
+
+    let (_, radius) = orbit(&mut app);
+    assert!((radius - ORBIT_MIN_RADIUS).abs() < TOLERANCE, "{}", radius);
+    // 頭の上を押しても、頭より小さくは回らない
+    assert!(radius >= HEAD_SIZE, "{}", radius);
+}
+
+#[test]
+fn pressing_close_to_the_head_spirals_out_to_the_minimum_radius() {
+    let mut app = common::headless_app();
+    play(&mut app);
+    let mut center = head(&app);
+    center.y += ORBIT_MIN_RADIUS / 4.0;
+    press(&mut app, center, 0.0);
+
+    let (orbit_center, radius) = orbit(&mut app);
+    assert_eq!(orbit_center, center);
+    assert!((radius - ORBIT_MIN_RADIUS).abs() < TOLERANCE, "{}", radius);
+}
+
+#[test]
+fn pressing_far_away_spirals_in_to_the_maximum_radius() {
+    let mut app = common::headless_app();
+    play(&mut app);
+    let mut center = head(&app);
+    center.y += ORBIT_MAX_RADIUS * 1.5;
+    press(&mut app, center, 0.0);
+
+    let (orbit_center, radius) = orbit(&mut app);
+    assert_eq!(orbit_center, center);
+    assert!((radius - ORBIT_MAX_RADIUS).abs() < TOLERANCE, "{}", radius);
+}
+
+#[test]
+fn tightening_spirals_in_around_the_same_center_down_to_the_minimum() {
+    let mut app = common::headless_app();
+    play(&mut app);
+    let mut center = head(&app);
+    center.y += ORBIT_MAX_RADIUS / 2.0;
+    press(&mut app, center, 1.0);
+
+    let (orbit_center, radius) = orbit(&mut app);
+    assert!(matches!(movement(&app), Movement::Spiral(_)));
+    assert_eq!(orbit_center, center);
+    assert!((radius - ORBIT_MIN_RADIUS).abs() < TOLERANCE, "{}", radius);
+}
+
+#[test]
+fn loosening_spirals_out_up_to_the_maximum() {
+    let mut app = common::headless_app();
+    play(&mut app);
+    let mut center = head(&app);
+    center.y += ORBIT_MAX_RADIUS / 2.0;
+    press(&mut app, center, -1.0);
+
+    let (orbit_center, radius) = orbit(&mut app);
+    assert_eq!(orbit_center, center);
+    assert!((radius - ORBIT_MAX_RADIUS).abs() < TOLERANCE, "{}", radius);
+}
+
+#[test]
+fn letting_go_of_the_spiral_keeps_the_radius_reached() {
+    let mut app = common::headless_app();
+    play(&mut app);
+    let start = ORBIT_MAX_RADIUS / 2.0;
+    let mut center = head(&app);
+    center.y += start;
+    press(&mut app, center, 1.0);
+    // 一秒だけ締める
+    let (_, tightened) = orbit_for(&mut app, (1.0 / common::STEP) as usize);
+    assert!((start - tightened - SPIRAL_SPEED).abs() < SPIRAL_SPEED * common::STEP as f32 * 2.0);
+
+    press(&mut app, center, 0.0);
+    let (orbit_center, radius) = orbit(&mut app);
+    assert!(matches!(movement(&app), Movement::Circular(_)));
+    assert_eq!(orbit_center, center);
+    assert!(
+        (radius - tightened).abs() < TOLERANCE,
+        "{} {}",
+        radius,
+        tightened
+    );
+}
@@ -0,0 +1,488 @@
+// 頭と門を置いて数フレーム進め、SEND_EVENTで送ったイベントがRECEIVE_EVENTで尾と点数に届くまでを確かめる
+mod common;
+
+use space_centipede::{event::*, *};
+
+// 門に届くまでに待つ上限
+const MAX_TICKS: usize = 600;
+
+// 真ん中から右向きに出発し、門だけが置かれたステージで始める
+fn play(app: &mut App, gates: Vec<level::GateSpec>) {
+    let mut stage = level::Level::new("pipeline".to_string(), Board::default());
+    stage.gates = gates;
+    common::start(app, stage);
+    assert!(app
+        .resources
+        .get::<CentipedeContainer>()
+        .unwrap()
+        .alive(PlayerId(0))
+        .is_some());
+}
+
+fn press(app: &mut App, position: Position) {
+    app.resources.get_mut::<input::Controls>().unwrap().set(
+        input::InputSource::Mouse,
+        input::Control {
+            position,
+            pressed: true,
+            ..Default::default()
+        },
+    );
+}
+
+// eventが届いたフレームまで進め、届いたeventを返す
+fn step_until<T: Clone + Send + Sync + 'static>(app: &mut App) -> Vec<T> {
+    let mut reader = app.resources.get::<Events<T>>().unwrap().get_reader();
+    for _ in 0..MAX_TICKS {
+        app.update();
+        let events = app.resources.get::<Events<T>>().unwrap();
+        let received: Vec<T> = reader.iter(&events).cloned().collect();
+        if !received.is_empty() {
+            return received;
+        }
+    }
+    panic!("no event in {} ticks", MAX_TICKS);
+}
+
+fn tail_count(app: &App) -> usize {
+    app.resources
+        .get::<CentipedeContainer>()
+        .unwrap()
+        .alive(PlayerId(0))
+        .expect("still alive")
+        .tail_count
+}
+
+// 残っている尾の番号。小さい順
+fn tail_indexes(app: &App) -> Vec<usize> {
+    let mut indexes: Vec<usize> = app
+        .world
+        .query::<&tail::LivingTail>()
+        .map(|tail| tail.index)
+        .collect();
+    indexes.sort_unstable();
+    indexes
+}
+
+fn gate_count(app: &App) -> usize {
+    app.world.query::<&gate::Gate>().count()
+}
+
+#[test]
+fn passing_a_gate_grows_the_tail_and_scores() {
+    // 進む先に縦向きの門
+    let mut app = common::headless_app();
+    play(
+        &mut app,
+        vec![level::GateSpec {
+            x: 200.0,
+            y: 0.0,
+            width: 150.0,
+            rotation: 90.0,
+        }],
+    );
+    assert_eq!(gate_count(&app), 1);
+
+    let events = step_until::<ThroughGate>(&mut app);
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.player, PlayerId(0));
+    // くぐった門と頭の様子がイベントに入っている
+    assert!((event.gate.width - 150.0).abs() < 0.1, "{:?}", event.gate);
+    assert!((event.gate.rotation - 90.0).abs() < 0.1, "{:?}", event.gate);
+    assert!(
+        (event.position.x - 200.0).abs() < 0.1,
+        "{:?}",
+        event.position
+    );
+    assert!(event.position.y.abs() < 0.1, "{:?}", event.position);
+    assert_eq!(event.head.speed, DEFAULT_SPEED);
+    assert!(matches!(event.head.movement, Movement::Linear(_)));
+    assert_eq!(event.head.tails_before, INITIAL_CENTIPEDE_LENGTH);
+    assert_eq!(event.head.tails_after, INITIAL_CENTIPEDE_LENGTH + 1);
+
+    assert_eq!(tail_count(&app), INITIAL_CENTIPEDE_LENGTH + 1);
+    assert_eq!(
+        tail_indexes(&app),
+        (0..=INITIAL_CENTIPEDE_LENGTH).collect::<Vec<_>>()
+    );
+    assert_eq!(gate_count(&app), 0);
+
+    // 点数は次のフレームのLASTで、伸びた後の尾の数で数える
+    app.update();
+    let status = app.resources.get::<ui::Status>().unwrap();
+    assert_eq!(status.get(PlayerId(0)).gates, 1);
+    assert_eq!(
+        status.get(PlayerId(0)).score,
+        ((INITIAL_CENTIPEDE_LENGTH + 1) as f32 * DEFAULT_SPEED / 100.0) as usize
+    );
+}
+
+#[test]
+fn hitting_a_poll_halves_the_tail_and_removes_the_gate() {
+    // 横向きの門の手前の柱が、進む先にある
+    let mut app = common::headless_app();
+    play(
+        &mut app,
+        vec![level::GateSpec {
+            x: 275.0,
+            y: 0.0,
+            width: 150.0,
+            rotation: 0.0,
+        }],
+    );
+
+    let events = step_until::<CrushPoll>(&mut app);
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.player, PlayerId(0));
+    assert_eq!(event.cause, CrushCause::Poll);
+    let gate = event.gate.expect("crushed into a gate");
+    assert!((gate.width - 150.0).abs() < 0.1, "{:?}", gate);
+    assert!(gate.rotation.abs() < 0.1, "{:?}", gate);
+    assert_eq!(event.head.tails_before, INITIAL_CENTIPEDE_LENGTH);
+    assert_eq!(event.head.tails_after, INITIAL_CENTIPEDE_LENGTH / 2);
+
+    assert_eq!(tail_count(&app), INITIAL_CENTIPEDE_LENGTH / 2);
+    assert_eq!(
+        tail_indexes(&app),
+        (0..INITIAL_CENTIPEDE_LENGTH / 2).collect::<Vec<_>>()
+    );
+    assert_eq!(gate_count(&app), 0);
+    assert_eq!(
+        app.resources
+            .get::<ui::Status>()
+            .unwrap()
+            .get(PlayerId(0))
+            .gates,
+        0
+    );
+}
+
+#[test]
+fn eating_the_own_tail_purges_from_the_eaten_index() {
+    let mut app = common::headless_app();
+    play(&mut app, vec![]);
+    // 尾の長さより短い円周で回り、自分の尾に頭から突っ込む
+    press(
+        &mut app,
+        Position {
+            x: 0.0,
+            y: 30.0,
+            visible: true,
+        },
+    );
+
+    let events = step_until::<EatTail>(&mut app);
+    let eaten = events
+        .iter()
+        .map(|event| {
+            assert_eq!(event.player, PlayerId(0));
+            assert_eq!(event.eater, Some(PlayerId(0)));
+            event.tail_index
+        })
+        .min()
+        .unwrap();
+    assert!(eaten > 0 && eaten < INITIAL_CENTIPEDE_LENGTH);
+
+    assert_eq!(tail_count(&app), eaten);
+    assert_eq!(tail_indexes(&app), (0..eaten).collect::<Vec<_>>());
+}
+
+#[test]
+fn a_fast_head_cannot_jump_over_a_poll() {
+    // 縦向きの門の下の柱が進む先にあり、一刻みで柱の太さより長く進む
+    let mut app = common::headless_app();
+    play(
+        &mut app,
+        vec![level::GateSpec {
+            x: 275.0,
+            y: 75.0,
+            width: 150.0,
+            rotation: 90.0,
+        }],
+    );
+    let step = 170.0;
+    app.resources
+        .get_mut::<CentipedeContainer>()
+        .unwrap()
+        .alive_mut(PlayerId(0))
+        .unwrap()
+        .speed = step / common::STEP as f32;
+    assert!(step > (POLL_SIZE + HEAD_SIZE) * 2.0);
+
+    let events = step_until::<CrushPoll>(&mut app);
+    assert_eq!(events.len(), 1);
+    assert_eq!(gate_count(&app), 0);
+}
+
+#[test]
+fn a_gate_crossed_by_two_heads_in_one_tick_goes_to_the_earlier_crossing() {
+    // 縦向きの門に、上下に並んだ二匹が右向きに進む
+    let mut app = common::headless_app();
+    let mut stage = level::Level::new("tie".to_string(), Board::default());
+    stage.gates = vec![level::GateSpec {
+        x: 200.0,
+        y: 0.0,
+        width: 200.0,
+        rotation: 90.0,
+    }];
+    common::start_with(
+        &mut app,
+        stage,
+        vec![input::InputSource::Mouse, input::InputSource::Keyboard],
+    );
+
+    // 同じ刻みでくぐるが、先に判定される0番は刻みの終わり近く、1番は始め近くでくぐる
+    let step = DEFAULT_SPEED * common::STEP as f32;
+    for (player, x) in &[
+        (PlayerId(0), 200.0 - step * 10.8),
+        (PlayerId(1), 200.0 - step * 10.2),
+    ] {
+        let head_entity = {
+            let mut centipede_container = app.resources.get_mut::<CentipedeContainer>().unwrap();
+            let centipede = centipede_container.alive_mut(*player).unwrap();
+            let dx = x - centipede.head().x;
+            for position in centipede.position_history.iter_mut() {
+                position.x += dx;
+            }
+            centipede.head_entity
+        };
+        let head = app
+            .resources
+            .get::<CentipedeContainer>()
+            .unwrap()
+            .alive(*player)
+            .unwrap()
+            .head();
+        *app.world.get_mut::<Position>(head_entity).unwrap() = head;
+    }
+
+    let events = step_until::<ThroughGate>(&mut app);
+    let players: Vec<_> = events.iter().map(|event| event.player).collect();
+    assert_eq!(players, vec![PlayerId(1)]);
+    assert_eq!(gate_count(&app), 0);
+
+    let centipede_container = app.resources.get::<CentipedeContainer>().unwrap();
+    assert_eq!(
+        centipede_container.alive(PlayerId(0)).unwrap().tail_count,
+        INITIAL_CENTIPEDE_LENGTH
+    );
+    assert_eq!(
+        centipede_container.alive(PlayerId(1)).unwrap().tail_count,
+        INITIAL_CENTIPEDE_LENGTH + 1
+    );
+}
+
+// playerをpositionからdirectionの向きに出発し直させる
+fn respawn(app: &mut App, player: PlayerId, position: Position, direction: Vec2) {
+    let head_entity = {
+        let mut centipede_container = app.resources.get_mut::<CentipedeContainer>().unwrap();
+        let centipede = centipede_container.alive_mut(player).unwrap();
+        *centipede = Alive::new(
+            player,
+            centipede.input,
+            centipede.head_entity,
+            position,
+            direction,
+        );
+        centipede.head_entity
+    };
+    *app.world.get_mut::<Position>(head_entity).unwrap() = position;
+}
+
+#[test]
+fn cutting_the_rival_tail_scores_the_stolen_tails_in_versus() {
+    let mut app = common::headless_app();
+    app.resources.get_mut::<mode::Session>().unwrap().mode = mode::GameMode::Versus;
+    common::start_with(
+        &mut app,
+        level::Level::new("steal".to_string(), Board::default()),
+        vec![input::InputSource::Mouse, input::InputSource::Keyboard],
+    );
+
+    // 1番を、右へ進む0番の尾の列の上から下向きに突っ込ませる
+    let head = app
+        .resources
+        .get::<CentipedeContainer>()
+        .unwrap()
+        .alive(PlayerId(0))
+        .unwrap()
+        .head();
+    respawn(
+        &mut app,
+        PlayerId(1),
+        Position {
+            x: head.x - TAIL_DISTANCE * 4.0,
+            y: head.y + TAIL_DISTANCE,
+            visible: true,
+        },
+        Vec2 { x: 0.0, y: -1.0 },
+    );
+
+    let events = step_until::<EatTail>(&mut app);
+    assert_eq!(events.len(), 1);
+    let bite = &events[0];
+    assert_eq!(bite.player, PlayerId(0));
+    assert_eq!(bite.eater, Some(PlayerId(1)));
+    assert_eq!(bite.tails_before, INITIAL_CENTIPEDE_LENGTH);
+    assert!(bite.tail_index > 0 && bite.tail_index < INITIAL_CENTIPEDE_LENGTH);
+    let stolen = INITIAL_CENTIPEDE_LENGTH - bite.tail_index;
+
+    // 尾はRECEIVE_EVENTで切れて移り、点は次のフレームのLASTで切れる前の長さから数える
+    {
+        let centipede_container = app.resources.get::<CentipedeContainer>().unwrap();
+        assert_eq!(
+            centipede_container.alive(PlayerId(0)).unwrap().tail_count,
+            bite.tail_index
+        );
+        assert_eq!(
+            centipede_container.alive(PlayerId(1)).unwrap().tail_count,
+            INITIAL_CENTIPEDE_LENGTH + stolen
+        );
+    }
+    app.update();
+    let status = app.resources.get::<ui::Status>().unwrap();
+    let points = (stolen * VERSUS_STEAL_POINTS) as isize;
+    assert_eq!(
+        status.breakdown(PlayerId(1)),
+        vec![(ScoreReason::Steal, points)]
+    );
+    assert_eq!(status.get(PlayerId(1)).score as isize, points);
+    assert_eq!(status.get(PlayerId(0)).score, 0);
+}
+
+fn popup_texts(app: &App) -> Vec<String> {
+    app.world
+        .query::<(&popup::Popup, &Text)>()
+        .map(|(_, text)| text.value.clone())
+        .collect()
+}
+
+#[test]
+fn hitting_a_poll_pops_up_the_lost_tails() {
+    let mut app = common::headless_app();
+    play(
+        &mut app,
+        vec![level::GateSpec {
+            x: 275.0,
+            y: 0.0,
+            width: 150.0,
+            rotation: 0.0,
+        }],
+    );
+
+    let events = step_until::<CrushPoll>(&mut app);
+    assert_eq!(events[0].head.tails_before, 10);
+    // 数字は次のフレームのLASTで、減る前後の数から出る
+    app.update();
+    assert_eq!(popup_texts(&app), vec![format!("{} -{}", CRASH_TEXT, 5)]);
+}
+
+#[test]
+fn eating_the_own_tail_pops_up_the_cut_tails() {
+    let mut app = common::headless_app();
+    play(&mut app, vec![]);
+    press(
+        &mut app,
+        Position {
+            x: 0.0,
+            y: 30.0,
+            visible: true,
+        },
+    );
+
+    let events = step_until::<EatTail>(&mut app);
+    let eaten = events.iter().map(|event| event.tail_index).min().unwrap();
+    app.update();
+    assert_eq!(
+        popup_texts(&app),
+        vec![format!("-{}", INITIAL_CENTIPEDE_LENGTH - eaten)]
+    );
+}
+
+#[test]
+fn two_enemies_on_the_same_tail_cut_it_once() {
+    let mut app = common::headless_app();
+    play(&mut app, vec![]);
+    app.update();
+
+    // 止まった敵を二体、同じ尾の上に置く
+    let (index, position) = app
+        .world
+        .query::<(&tail::LivingTail, &Position)>()
+        .filter(|(tail, position)| tail.index == 5 && position.visible)
+        .map(|(tail, position)| (tail.index, *position))
+        .next()
+        .expect("the tail is out");
+    for _ in 0..2 {
+        app.world.spawn((
+            enemy::Enemy::new(enemy::EnemyKind::Bouncer, Vec2 { x: 1.0, y: 0.0 }, 0.0),
+            position,
+        ));
+    }
+
+    let events = step_until::<EatTail>(&mut app);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].eater, None);
+    assert_eq!(events[0].tail_index, index);
+    assert_eq!(tail_count(&app), index);
+    assert_eq!(app.world.query::<&enemy::Enemy>().count(), 0);
+
+    app.update();
+    assert_eq!(
+        popup_texts(&app),
+        vec![format!("-{}", INITIAL_CENTIPEDE_LENGTH - index)]
+    );
+}
+
+#[test]
+fn a_fast_head_cannot_jump_over_a_moving_tail() {
+    let mut app = common::headless_app();
+    common::start_with(
+        &mut app,
+        level::Level::new("sweep".to_string(), Board::default()),
+        vec![input::InputSource::Mouse, input::InputSource::Keyboard],
+    );
+    app.update();
+
+    // 0番を、右へ進む1番の尾の真上から下向きに、一刻みで尾の列を飛び越える速さで突っ込ませる
+    let (index, tail) = app
+        .world
+        .query::<(&tail::LivingTail, &Position)>()
+        .filter(|(tail, position)| {
+            tail.player == PlayerId(1) && tail.index == 5 && position.visible
+        })
+        .map(|(tail, position)| (tail.index, *position))
+        .next()
+        .expect("the tail is out");
+    let step = 120.0;
+    assert!(step > (TAIL_SIZE + HEAD_SIZE) * 2.0);
+    respawn(
+        &mut app,
+        PlayerId(0),
+        Position {
+            x: tail.x,
+            y: tail.y + step / 2.0,
+            visible: true,
+        },
+        Vec2 { x: 0.0, y: -1.0 },
+    );
+    app.resources
+        .get_mut::<CentipedeContainer>()
+        .unwrap()
+        .alive_mut(PlayerId(0))
+        .unwrap()
+        .speed = step / common::STEP as f32;
+
+    // 一刻み目で尾の列をまたぐ。置き直した0番の古い尾は見ない
+    let mut reader = app.resources.get::<Events<EatTail>>().unwrap().get_reader();
+    app.update();
+    let events = app.resources.get::<Events<EatTail>>().unwrap();
+    let bites: Vec<_> = reader
+        .iter(&events)
+        .filter(|event| event.player == PlayerId(1))
+        .map(|event| (event.eater, event.tail_index))
+        .collect();
+    assert_eq!(bites, vec![(Some(PlayerId(0)), index)]);
+}
@@ -0,0 +1,153 @@
+// 門をくぐった場所と激突した場所に数字が浮かび、時間が来たら消えることを確かめる
+mod common;
+
+use space_centipede::{event::*, popup::Popup, *};
+
+// 何も置かれていないステージで始める
+fn play(app: &mut App) {
+    common::start(
+        app,
+        level::Level::new("popup".to_string(), Board::default()),
+    );
+}
+
+fn popups(app: &App) -> Vec<(Position, String, Color)> {
+    app.world
+        .query::<(&Popup, &Text)>()
+        .map(|(popup, text)| (popup.position, text.value.clone(), popup.color))
+        .collect()
+}
+
+fn tails(app: &App) -> usize {
+    app.resources
+        .get::<CentipedeContainer>()
+        .unwrap()
+        .alive(PlayerId(0))
+        .unwrap()
+        .tail_count
+}
+
+// 今の頭の様子。尾の数はtails_afterになる
+fn head(app: &App, tails_after: usize) -> HeadFacts {
+    HeadFacts::new(
+        app.resources
+            .get::<CentipedeContainer>()
+            .unwrap()
+            .alive(PlayerId(0))
+            .unwrap(),
+        tails_after,
+    )
+}
+
+// positionを真ん中にした縦向きの門
+fn gate(position: Position) -> GateFacts {
+    let poll = |dy: f32| Position {
+        y: position.y + dy,
+        ..position
+    };
+    GateFacts::new(Entity::new(0), &poll(75.0), &poll(-75.0))
+}
+
+#[test]
+fn passing_a_gate_pops_up_the_points_and_multiplier_where_it_passed() {
+    let mut app = common::headless_app();
+    play(&mut app);
+    let tails = tails(&app);
+    let position = Position {
+        x: 30.0,
+        y: -20.0,
+        visible: true,
+    };
+    app.resources
+        .get_mut::<Events<ThroughGate>>()
+        .unwrap()
+        .send(ThroughGate {
+            player: PlayerId(0),
+            gate: gate(position),
+            position,
+            head: head(&app, tails + 1),
+        });
+    app.update();
+
+    assert_eq!(
+        popups(&app),
+        vec![(position, format!("+{:}  x1.0", tails + 1), POPUP_COLOR)]
+    );
+
+    for _ in 0..(POPUP_SECONDS as f64 / common::STEP).ceil() as usize + 1 {
+        app.update();
+    }
+    assert_eq!(popups(&app), vec![]);
+}
+
+#[test]
+fn the_multiplier_is_the_speed_when_the_gate_was_passed() {
+    let mut app = common::headless_app();
+    play(&mut app);
+    let tails = tails(&app);
+    let position = Position {
+        x: 30.0,
+        y: -20.0,
+        visible: true,
+    };
+    let mut passed = head(&app, tails + 1);
+    passed.speed = 250.0;
+    app.resources
+        .get_mut::<Events<ThroughGate>>()
+        .unwrap()
+        .send(ThroughGate {
+            player: PlayerId(0),
+            gate: gate(position),
+            position,
+            head: passed,
+        });
+    // 点が付いてから数字が出るまでに、速さが変わる
+    app.resources
+        .get_mut::<CentipedeContainer>()
+        .unwrap()
+        .alive_mut(PlayerId(0))
+        .unwrap()
+        .speed = 120.0;
+    app.update();
+
+    assert_eq!(
+        popups(&app),
+        vec![(
+            position,
+            format!("+{:}  x2.5", ((tails + 1) as f32 * 2.5).floor()),
+            POPUP_COLOR
+        )]
+    );
+}
+
+#[test]
+fn crashing_pops_up_the_lost_tails_in_red() {
+    let mut app = common::headless_app();
+    play(&mut app);
+    let tails = tails(&app);
+    let position = Position {
+        x: -50.0,
+        y: 10.0,
+        visible: true,
+    };
+    app.resources
+        .get_mut::<Events<CrushPoll>>()
+        .unwrap()
+        .send(CrushPoll {
+            player: PlayerId(0),
+            cause: CrushCause::Poll,
+            gate: Some(gate(position)),
+            position,
+            head: head(&app, tails / 2),
+        });
+    app.update();
+
+    assert_eq!(
+        popups(&app),
+        vec![(
+            position,
+            format!("{:} -{:}", CRASH_TEXT, tails - tails / 2),
+            POPUP_LOSS_COLOR
+        )]
+    );
+}
@@ -0,0 +1,121 @@
+// 門をくぐったイベントから点が付き、内訳とスコアが揃うこと、遊び方ごとの決まりが差し替えられることを確かめる
+mod common;
+
+use space_centipede::{event::*, score::*, *};
+
+// 何も置かれていないステージで始める
+fn play(app: &mut App) {
+    common::start(
+        app,
+        level::Level::new("score".to_string(), Board::default()),
+    );
+}
+
+// 今の頭で門をくぐったことにして一刻み進め、付いた点
+fn through_gate(app: &mut App) -> Vec<ScoreAwarded> {
+    let mut reader = app
+        .resources
+        .get::<Events<ScoreAwarded>>()
+        .unwrap()
+        .get_reader();
+    let event = {
+        let centipede_container = app.resources.get::<CentipedeContainer>().unwrap();
+        let centipede = centipede_container.alive(PlayerId(0)).unwrap();
+        let head = centipede.head();
+        let poll = |y: f32| Position { y, ..head };
+        ThroughGate {
+            player: PlayerId(0),
+            gate: GateFacts::new(Entity::new(0), &poll(75.0), &poll(-75.0)),
+            position: head,
+            head: HeadFacts::new(centipede, centipede.tail_count + 1),
+        }
+    };
+    app.resources
+        .get_mut::<Events<ThroughGate>>()
+        .unwrap()
+        .send(event);
+    app.update();
+    let events = app.resources.get::<Events<ScoreAwarded>>().unwrap();
+    reader.iter(&events).cloned().collect()
+}
+
+#[test]
+fn a_gate_is_scored_by_tails_and_speed() {
+    let mut app = common::headless_app();
+    play(&mut app);
+    // 点は門をくぐって伸びた後の尾の数で数える
+    let tails = {
+        let mut centipede_container = app.resources.get_mut::<CentipedeContainer>().unwrap();
+        let centipede = centipede_container.alive_mut(PlayerId(0)).unwrap();
+        centipede.speed = DEFAULT_SPEED * 1.5;
+        centipede.tail_count as isize + 1
+    };
+
+    let awards: Vec<_> = through_gate(&mut app)
+        .iter()
+        .map(|award| (award.player, award.reason, award.points))
+        .collect();
+    assert_eq!(
+        awards,
+        vec![
+            (PlayerId(0), ScoreReason::Gate, tails),
+            (PlayerId(0), ScoreReason::Speed, tails / 2),
+        ]
+    );
+
+    let status = app.resources.get::<ui::Status>().unwrap();
+    assert_eq!(status.get(PlayerId(0)).score as isize, tails + tails / 2);
+    assert_eq!(status.get(PlayerId(0)).gates, 1);
+    assert_eq!(
+        status.breakdown(PlayerId(0)),
+        vec![(ScoreReason::Gate, tails), (ScoreReason::Speed, tails / 2)]
+    );
+}
+
+struct FlatRules;
+
+impl ScoringRules for FlatRules {
+    fn through_gate(&self, _: &ThroughGate) -> Vec<Award> {
+        vec![(7, ScoreReason::Gate)]
+    }
+}
+
+#[test]
+fn rules_registered_for_the_mode_replace_the_standard_ones() {
+    let mut app = common::headless_app();
+    app.resources
+        .get_mut::<Scoring>()
+        .unwrap()
+        .register(mode::GameMode::Endless, FlatRules);
+    play(&mut app);
+
+    let awards = through_gate(&mut app);
+    assert_eq!(awards.len(), 1);
+    assert_eq!(awards[0].points, 7);
+    assert_eq!(
+        app.resources
+            .get::<ui::Status>()
+            .unwrap()
+            .get(PlayerId(0))
+            .score,
+        7
+    );
+}
+
+#[test]
+fn stealing_scores_the_tails_cut_off_before_the_cut() {
+    let bite = |eater: Option<PlayerId>| EatTail {
+        player: PlayerId(0),
+        tail_index: 4,
+        eater,
+        position: Position::default(true),
+        tails_before: 10,
+    };
+    assert_eq!(
+        VersusRules.eat_tail(&bite(Some(PlayerId(1)))),
+        vec![((6 * VERSUS_STEAL_POINTS) as isize, ScoreReason::Steal)]
+    );
+    // 自分の尾と、敵が切った尾は点にならない
+    assert_eq!(VersusRules.eat_tail(&bite(Some(PlayerId(0)))), vec![]);
+    assert_eq!(VersusRules.eat_tail(&bite(None)), vec![]);
+}
@@ -0,0 +1,127 @@
+// 同じシードなら、どう動いても、何点取っても同じ場所に同じ順でランダムな門が出ることを確かめる
+mod common;
+
+use space_centipede::*;
+use std::collections::HashSet;
+
+const SEED: u64 = 7;
+// 遊ばせる秒数
+const SECONDS: f64 = 8.0;
+// 決まりどおりのdirectorで遊ばせる秒数。門の間隔が長いので長めに遊ぶ
+const DEFAULT_DIRECTOR_SECONDS: f64 = 30.0;
+// 少なくともこれだけの門を比べる
+const MIN_GATES: usize = 10;
+
+// 幅も間隔も時間で変わらず、普通の門だけを出す
+fn director() -> director::Director {
+    director::Director {
+        min_gates: 0,
+        max_gates: 100,
+        interval: Curve::constant(0.5),
+        min_width: Curve::constant(140.0),
+        max_width: Curve::constant(140.0),
+        kinds: vec![director::KindWeight {
+            kind: gate::GateKind::Normal,
+            by_time: Curve::constant(1.0),
+        }],
+    }
+}
+
+// 出てきた門の種類と位置と向き
+type Rolled = (gate::GateKind, Position, Quat);
+
+// directorに従い、controlで動かし続けてseconds遊ぶ。刻みごとにscoreで点を書き換える
+// 出てきた順に門を返す。死んだらそこまで
+fn gates(
+    director: Option<director::Director>,
+    control: input::Control,
+    seconds: f64,
+    score: impl Fn(usize) -> Option<usize>,
+) -> Vec<Rolled> {
+    let mut app = common::headless_app();
+    {
+        let mut session = app.resources.get_mut::<mode::Session>().unwrap();
+        session.seed = SEED;
+        session.seed_locked = true;
+    }
+    let mut stage = level::Level::new("seed".to_string(), Board::default());
+    stage.random_gates = true;
+    stage.director = director;
+    common::start(&mut app, stage);
+    app.resources
+        .get_mut::<input::Controls>()
+        .unwrap()
+        .set(input::InputSource::Mouse, control);
+
+    let mut seen = HashSet::new();
+    let mut rolled = vec![];
+    for tick in 0..(seconds / common::STEP) as usize {
+        if let Some(score) = score(tick) {
+            app.resources
+                .get_mut::<ui::Status>()
+                .unwrap()
+                .players
+                .entry(PlayerId(0))
+                .or_default()
+                .score = score;
+        }
+        app.update();
+        if app
+            .resources
+            .get::<CentipedeContainer>()
+            .unwrap()
+            .alive(PlayerId(0))
+            .is_none()
+        {
+            break;
+        }
+        for (entity, gate, position, transform) in
+            app.world
+                .query::<(Entity, &gate::Gate, &Position, &Transform)>()
+        {
+            if seen.insert(entity) {
+                rolled.push((gate.kind, *position, transform.rotation));
+            }
+        }
+    }
+    rolled
+}
+
+// 出発地点のそばを回り続ける
+fn orbiting() -> input::Control {
+    input::Control {
+        position: Position {
+            x: 0.0,
+            y: 60.0,
+            visible: true,
+        },
+        pressed: true,
+        ..Default::default()
+    }
+}
+
+fn assert_same_start(a: &[Rolled], b: &[Rolled]) {
+    let count = a.len().min(b.len());
+    assert!(count >= MIN_GATES, "{} {}", a.len(), b.len());
+    assert_eq!(a[..count], b[..count]);
+}
+
+#[test]
+fn the_same_seed_places_the_same_gates_whatever_the_input() {
+    // まっすぐ進んで壁で跳ね返るのと、出発地点のそばを回り続けるのとで比べる
+    let straight = gates(Some(director()), input::Control::default(), SECONDS, |_| {
+        None
+    });
+    let orbiting = gates(Some(director()), orbiting(), SECONDS, |_| None);
+    assert_same_start(&straight, &orbiting);
+}
+
+#[test]
+fn the_same_seed_places_the_same_gates_whatever_the_score() {
+    // 決まりどおりのdirectorで、点を取らないのと、どんどん点を取るのとで比べる
+    let no_score = gates(None, orbiting(), DEFAULT_DIRECTOR_SECONDS, |_| Some(0));
+    let high_score = gates(None, orbiting(), DEFAULT_DIRECTOR_SECONDS, |tick| {
+        Some(tick * 10)
+    });
+    assert_same_start(&no_score, &high_score);
+}
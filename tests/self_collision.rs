@@ -0,0 +1,147 @@
+// 小さく回って自分の尾に頭を重ね、自分の尾との当たり判定の決まりを確かめる
+mod common;
+
+use space_centipede::{event::*, interaction::SelfCollision, *};
+
+const TICKS: usize = 300;
+
+// 何も置かれていないステージで、決まりを変えて始める
+fn play(app: &mut App, self_collision: SelfCollision) {
+    app.resources.insert(self_collision);
+    // 回る半径の下限より小さく回って確かめるので、半径は押したとおりにする
+    app.resources.insert(head::OrbitLimits {
+        min_radius: 0.0,
+        max_radius: f32::INFINITY,
+    });
+    common::start(
+        app,
+        level::Level::new("self collision".to_string(), Board::default()),
+    );
+}
+
+// 進む向きの左側、radius離れたところを中心にして回り続ける
+fn orbit(app: &mut App, radius: f32) {
+    let (head, direction) = {
+        let centipede_container = app.resources.get::<CentipedeContainer>().unwrap();
+        let centipede = centipede_container.alive(PlayerId(0)).unwrap();
+        (
+            *centipede.position_history.last().unwrap(),
+            centipede.last_move.normalize(),
+        )
+    };
+    app.resources.get_mut::<input::Controls>().unwrap().set(
+        input::InputSource::Mouse,
+        input::Control {
+            position: Position {
+                x: head.x - direction.y * radius,
+                y: head.y + direction.x * radius,
+                visible: true,
+            },
+            pressed: true,
+            ..Default::default()
+        },
+    );
+}
+
+// 刻みごとに、自分で自分の尾を切ったイベント
+fn own_bites(app: &mut App) -> Vec<Vec<usize>> {
+    let mut reader = app.resources.get::<Events<EatTail>>().unwrap().get_reader();
+    (0..TICKS)
+        .map(|_| {
+            app.update();
+            let events = app.resources.get::<Events<EatTail>>().unwrap();
+            reader
+                .iter(&events)
+                .filter(|event| event.eater == Some(event.player))
+                .map(|event| event.tail_index)
+                .collect()
+        })
+        .collect()
+}
+
+// 半径の二倍が頭の大きさより少し大きい、一番小さく回れる円
+fn tight_radius() -> f32 {
+    HEAD_SIZE / 2.0 * 1.05
+}
+
+// 円周全部が頭に触れる円
+fn tiny_radius() -> f32 {
+    HEAD_SIZE / 2.0 * 0.7
+}
+
+fn tail_count(app: &App) -> usize {
+    app.resources
+        .get::<CentipedeContainer>()
+        .unwrap()
+        .alive(PlayerId(0))
+        .map_or(0, |centipede| centipede.tail_count)
+}
+
+#[test]
+fn the_first_tails_are_never_bitten() {
+    for grace_tails in [0, 1, 3].iter().copied() {
+        let mut app = common::headless_app();
+        play(&mut app, SelfCollision { grace_tails });
+        orbit(&mut app, tight_radius());
+
+        let bites: Vec<usize> = own_bites(&mut app).into_iter().flatten().collect();
+        assert!(!bites.is_empty(), "grace {} never bit", grace_tails);
+        assert!(
+            bites.iter().all(|index| *index >= grace_tails),
+            "grace {} bit {:?}",
+            grace_tails,
+            bites
+        );
+    }
+}
+
+#[test]
+fn a_tight_orbit_still_bites_beyond_the_grace_tails() {
+    // 円周全部が頭に触れる円でも、回る半径の下限の円でも、すぐ後ろの尾より先には当たる
+    for radius in [tiny_radius(), ORBIT_MIN_RADIUS].iter().copied() {
+        let mut app = common::headless_app();
+        play(&mut app, SelfCollision::default());
+        orbit(&mut app, radius);
+
+        let bites: Vec<usize> = own_bites(&mut app).into_iter().flatten().collect();
+        assert!(!bites.is_empty(), "radius {} never bit", radius);
+        assert!(
+            bites
+                .iter()
+                .all(|index| *index >= SELF_COLLISION_GRACE_TAILS),
+            "radius {} bit {:?}",
+            radius,
+            bites
+        );
+        assert!(
+            tail_count(&app) < INITIAL_CENTIPEDE_LENGTH,
+            "radius {}",
+            radius
+        );
+    }
+}
+
+#[test]
+fn touching_many_tails_at_once_is_one_bite() {
+    let mut app = common::headless_app();
+    play(&mut app, SelfCollision { grace_tails: 0 });
+    // 円周全部が頭に触れるので、毎刻みいくつもの尾に重なる
+    orbit(&mut app, tiny_radius());
+
+    let bites = own_bites(&mut app);
+    assert!(bites.iter().any(|bites| !bites.is_empty()));
+    for (tick, bites) in bites.iter().enumerate() {
+        assert!(bites.len() <= 1, "tick {}: {:?}", tick, bites);
+    }
+}
+
+#[test]
+fn an_orbit_wider_than_the_body_never_bites() {
+    let mut app = common::headless_app();
+    play(&mut app, SelfCollision::default());
+    let body = (INITIAL_CENTIPEDE_LENGTH + 1) as f32 * TAIL_DISTANCE + HEAD_SIZE;
+    orbit(&mut app, body / std::f32::consts::PI);
+
+    let bites: Vec<usize> = own_bites(&mut app).into_iter().flatten().collect();
+    assert_eq!(bites, Vec::<usize>::new());
+}
@@ -0,0 +1,218 @@
+// 描画せずに長い時間遊ばせて、毎回の刻みのあとで壊れてはいけない約束を確かめる
+// ふだんは一つの組み合わせを短く遊ばせる。何千分も遊ばせるときは
+//   cargo test --release --test soak -- --ignored
+// SOAK_MINUTESで分数を、SOAK_SEEDで出鱈目な入力のシードを変えられる
+mod common;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use space_centipede::{event::*, *};
+use std::collections::HashMap;
+
+// SOAK_MINUTESが無いときに、一つの組み合わせを遊ばせる分数。ふだんと、--ignoredを付けたとき
+const DEFAULT_MINUTES: f64 = 1.0;
+const LONG_MINUTES: f64 = 3000.0;
+// 頭が盤面からはみ出してよい幅
+const BOARD_MARGIN: f32 = PANEL_SIZE;
+// 実体の数と、頭の跡の長さの上限
+const MAX_ENTITIES: usize = 5000;
+const MAX_HISTORY: usize = 20000;
+
+fn minutes(default: f64) -> f64 {
+    std::env::var("SOAK_MINUTES")
+        .ok()
+        .and_then(|minutes| minutes.parse().ok())
+        .unwrap_or(default)
+}
+
+fn ticks(minutes: f64) -> usize {
+    (minutes * 60.0 / common::STEP) as usize
+}
+
+// 遊び方と参加者を変えて、最初からやり直す
+fn restart(app: &mut App, mode: mode::GameMode, players: Vec<input::InputSource>) {
+    app.resources.get_mut::<title::Attract>().unwrap().active = false;
+    app.resources.get_mut::<mode::Session>().unwrap().mode = mode;
+    let mut centipede_container = app.resources.get_mut::<CentipedeContainer>().unwrap();
+    let mut game_over_events = app.resources.get_mut::<Events<GameOver>>().unwrap();
+    centipede_container.players = players;
+    centipede_container.finish(0.0, GameOverCause::Aborted, &mut game_over_events);
+}
+
+fn modes() -> Vec<mode::GameMode> {
+    let mut modes = vec![mode::GameMode::Endless];
+    while modes.last().unwrap().next() != modes[0] {
+        modes.push(modes.last().unwrap().next());
+    }
+    modes
+}
+
+fn check_invariants(app: &App, tick: usize) {
+    let world = &app.world;
+    let board = app.resources.get::<Board>().unwrap();
+    let centipede_container = app.resources.get::<CentipedeContainer>().unwrap();
+
+    let mut tails: HashMap<PlayerId, usize> = HashMap::new();
+    for tail in world.query::<&tail::LivingTail>() {
+        *tails.entry(tail.player).or_default() += 1;
+    }
+
+    for centipede in centipede_container.iter_alive() {
+        assert_eq!(
+            tails.get(&centipede.player).copied().unwrap_or(0),
+            centipede.tail_count,
+            "tick {}: {:?} has a different number of tails",
+            tick,
+            centipede.player
+        );
+        assert!(
+            centipede.position_history.len() <= MAX_HISTORY,
+            "tick {}: {:?} keeps {} positions",
+            tick,
+            centipede.player,
+            centipede.position_history.len()
+        );
+
+        let head = world
+            .get::<Position>(centipede.head_entity)
+            .expect("head entity");
+        assert!(
+            head.x.abs() <= board.x_border() + BOARD_MARGIN
+                && head.y.abs() <= board.y_border() + BOARD_MARGIN,
+            "tick {}: {:?} left the board at {:?}",
+            tick,
+            centipede.player,
+            *head
+        );
+    }
+
+    for position in world.query::<&Position>() {
+        assert!(
+            position.x.is_finite() && position.y.is_finite(),
+            "tick {}: position {:?}",
+            tick,
+            *position
+        );
+    }
+    for transform in world.query::<&Transform>() {
+        assert!(
+            transform.translation.is_finite(),
+            "tick {}: transform {:?}",
+            tick,
+            *transform
+        );
+    }
+
+    let runs = app.resources.get::<ghost::Ghosts>().unwrap().runs();
+    assert!(runs <= GHOST_MAX_RUNS, "tick {}: {} ghost runs", tick, runs);
+
+    let entities = world.query::<Entity>().count();
+    assert!(
+        entities <= MAX_ENTITIES,
+        "tick {}: {} entities",
+        tick,
+        entities
+    );
+}
+
+fn play_attract_demo(minutes: f64) {
+    let mut app = common::headless_app();
+    for tick in 0..ticks(minutes) {
+        app.update();
+        check_invariants(&app, tick);
+    }
+}
+
+fn play_bots_in_every_mode(minutes: f64) {
+    let mut app = common::headless_app();
+    for mode in modes() {
+        let players = (0..mode.min_players().max(1))
+            .map(input::InputSource::Bot)
+            .collect();
+        restart(&mut app, mode, players);
+        for tick in 0..ticks(minutes) {
+            app.update();
+            check_invariants(&app, tick);
+        }
+    }
+}
+
+// マウスを出鱈目に動かし、ときどき頭の真上を押したり、回りながら締めたり緩めたりする
+fn play_random_input(minutes: f64) {
+    let seed = std::env::var("SOAK_SEED")
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or(0);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut app = common::headless_app();
+    restart(
+        &mut app,
+        mode::GameMode::Endless,
+        vec![input::InputSource::Mouse],
+    );
+
+    let mut control = input::Control::default();
+    for tick in 0..ticks(minutes) {
+        if rng.gen_bool(0.05) {
+            let (x_border, y_border) = {
+                let board = app.resources.get::<Board>().unwrap();
+                (board.x_border(), board.y_border())
+            };
+            let head = app
+                .resources
+                .get::<CentipedeContainer>()
+                .unwrap()
+                .head_entity(PlayerId(0))
+                .and_then(|entity| app.world.get::<Position>(entity).ok().copied());
+            control = input::Control {
+                position: match head {
+                    Some(head) if rng.gen_bool(0.1) => head,
+                    _ => Position {
+                        x: rng.gen_range(-x_border, x_border),
+                        y: rng.gen_range(-y_border, y_border),
+                        visible: true,
+                    },
+                },
+                pressed: rng.gen_bool(0.5),
+                spiral: if rng.gen_bool(0.3) {
+                    rng.gen_range(-1.0, 1.0)
+                } else {
+                    0.0
+                },
+                brake: rng.gen_bool(0.2),
+                boost: rng.gen_bool(0.05),
+            };
+        }
+        app.resources
+            .get_mut::<input::Controls>()
+            .unwrap()
+            .set(input::InputSource::Mouse, control);
+
+        app.update();
+        check_invariants(&app, tick);
+    }
+}
+
+#[test]
+fn attract_demo() {
+    play_attract_demo(minutes(DEFAULT_MINUTES));
+}
+
+#[test]
+fn bots_in_every_mode() {
+    play_bots_in_every_mode(minutes(DEFAULT_MINUTES));
+}
+
+#[test]
+fn random_input() {
+    play_random_input(minutes(DEFAULT_MINUTES));
+}
+
+// 時間がかかるので、--ignoredを付けたときだけ回す
+#[test]
+#[ignore]
+fn long_soak() {
+    let minutes = minutes(LONG_MINUTES);
+    play_attract_demo(minutes);
+    play_bots_in_every_mode(minutes);
+    play_random_input(minutes);
+}
@@ -0,0 +1,215 @@
+// 速さの上限と戻り方、ブレーキの支払い、ブーストの待ち時間を確かめる
+mod common;
+
+use space_centipede::{speed::SpeedModel, *};
+
+// 何も置かれていないステージで、速さの決まりを変えて始める
+fn play(app: &mut App, model: SpeedModel) {
+    app.resources.insert(model);
+    common::start(
+        app,
+        level::Level::new("speed".to_string(), Board::default()),
+    );
+}
+
+fn centipede<T>(app: &App, f: impl FnOnce(&Alive) -> T) -> T {
+    f(app
+        .resources
+        .get::<CentipedeContainer>()
+        .unwrap()
+        .alive(PlayerId(0))
+        .unwrap())
+}
+
+fn set_speed(app: &mut App, speed: f32) {
+    app.resources
+        .get_mut::<CentipedeContainer>()
+        .unwrap()
+        .alive_mut(PlayerId(0))
+        .unwrap()
+        .speed = speed;
+}
+
+fn control(app: &mut App, control: input::Control) {
+    app.resources
+        .get_mut::<input::Controls>()
+        .unwrap()
+        .set(input::InputSource::Mouse, control);
+}
+
+fn run(app: &mut App, seconds: f64) {
+    for _ in 0..(seconds / common::STEP).round() as usize {
+        app.update();
+    }
+}
+
+#[test]
+fn orbiting_speeds_up_only_to_the_cap() {
+    let cap = DEFAULT_SPEED + SPEED_UP;
+    let mut app = common::headless_app();
+    play(
+        &mut app,
+        SpeedModel {
+            cap,
+            decay: SPEED_DECAY,
+        },
+    );
+    let mut center = centipede(&app, |c| *c.position_history.last().unwrap());
+    center.y += ORBIT_MAX_RADIUS / 2.0;
+    control(
+        &mut app,
+        input::Control {
+            position: center,
+            pressed: true,
+            ..Default::default()
+        },
+    );
+
+    run(&mut app, 0.5);
+    let speed = centipede(&app, |c| c.speed);
+    assert!(speed > DEFAULT_SPEED && speed < cap, "{}", speed);
+
+    run(&mut app, 2.0);
+    assert_eq!(centipede(&app, |c| c.speed), cap);
+}
+
+#[test]
+fn moving_straight_slows_down_to_the_default_speed() {
+    let decay = 50.0;
+    let mut app = common::headless_app();
+    play(&mut app, SpeedModel { cap: 400.0, decay });
+    set_speed(&mut app, DEFAULT_SPEED + decay * 1.5);
+
+    run(&mut app, 1.0);
+    let speed = centipede(&app, |c| c.speed);
+    assert!(
+        (speed - (DEFAULT_SPEED + decay * 0.5)).abs() < 1.0,
+        "{}",
+        speed
+    );
+
+    run(&mut app, 1.0);
+    assert_eq!(centipede(&app, |c| c.speed), DEFAULT_SPEED);
+}
+
+#[test]
+fn braking_pays_with_score_then_with_tails() {
+    let mut app = common::headless_app();
+    play(&mut app, SpeedModel::default());
+    set_speed(&mut app, DEFAULT_SPEED * 2.0);
+    app.resources
+        .get_mut::<ui::Status>()
+        .unwrap()
+        .players
+        .entry(PlayerId(0))
+        .or_default()
+        .score = BRAKE_SCORE_COST + BRAKE_SCORE_COST / 2;
+    let tails = centipede(&app, |c| c.tail_count);
+    control(
+        &mut app,
+        input::Control {
+            brake: true,
+            ..Default::default()
+        },
+    );
+
+    // 二回払うだけ落とす
+    let seconds = (BRAKE_SPEED_PER_COST * 2.5 / BRAKE_DECELERATION) as f64;
+    run(&mut app, seconds);
+    let status = app.resources.get::<ui::Status>().unwrap().get(PlayerId(0));
+    assert_eq!(status.score, BRAKE_SCORE_COST / 2);
+    assert_eq!(
+        app.resources
+            .get::<ui::Status>()
+            .unwrap()
+            .breakdown(PlayerId(0)),
+        vec![(event::ScoreReason::Brake, -(BRAKE_SCORE_COST as isize))]
+    );
+    assert_eq!(centipede(&app, |c| c.tail_count), tails - 1);
+    let speed = centipede(&app, |c| c.speed);
+    let expected = DEFAULT_SPEED * 2.0 - BRAKE_DECELERATION * seconds as f32;
+    assert!((speed - expected).abs() < 2.0, "{} {}", speed, expected);
+
+    // 下限より遅くはならない
+    run(&mut app, 2.0);
+    assert_eq!(centipede(&app, |c| c.speed), BRAKE_MIN_SPEED);
+}
+
+#[test]
+fn boost_waits_for_its_cooldown() {
+    let mut app = common::headless_app();
+    play(
+        &mut app,
+        SpeedModel {
+            cap: 400.0,
+            decay: 0.0,
+        },
+    );
+    let boost = |app: &mut App| {
+        control(
+            app,
+            input::Control {
+                boost: true,
+                ..Default::default()
+            },
+        );
+        app.update();
+        control(app, input::Control::default());
+        app.update();
+        centipede(app, |c| c.speed)
+    };
+
+    assert_eq!(boost(&mut app), DEFAULT_SPEED + BOOST_SPEED);
+    // 待ち時間の間は押しても変わらない
+    run(&mut app, BOOST_COOLDOWN_SECONDS / 2.0);
+    assert_eq!(boost(&mut app), DEFAULT_SPEED + BOOST_SPEED);
+
+    run(&mut app, BOOST_COOLDOWN_SECONDS / 2.0);
+    assert_eq!(boost(&mut app), DEFAULT_SPEED + BOOST_SPEED * 2.0);
+}
+
+#[test]
+fn a_pinned_speed_ignores_brake_and_boost() {
+    let mut app = common::headless_app();
+    play(&mut app, SpeedModel::default());
+    app.resources
+        .get_mut::<mode::Session>()
+        .unwrap()
+        .speed_pinned = true;
+    set_speed(&mut app, DEFAULT_SPEED * 2.0);
+    app.resources
+        .get_mut::<ui::Status>()
+        .unwrap()
+        .players
+        .entry(PlayerId(0))
+        .or_default()
+        .score = BRAKE_SCORE_COST;
+    let tails = centipede(&app, |c| c.tail_count);
+
+    // 払うだけブレーキを踏み続けても、点も尾も速さも変わらない
+    control(
+        &mut app,
+        input::Control {
+            brake: true,
+            ..Default::default()
+        },
+    );
+    run(
+        &mut app,
+        (BRAKE_SPEED_PER_COST * 3.0 / BRAKE_DECELERATION) as f64,
+    );
+    control(
+        &mut app,
+        input::Control {
+            boost: true,
+            ..Default::default()
+        },
+    );
+    run(&mut app, 0.5);
+
+    assert_eq!(centipede(&app, |c| c.speed), DEFAULT_SPEED * 2.0);
+    assert_eq!(centipede(&app, |c| c.tail_count), tails);
+    let status = app.resources.get::<ui::Status>().unwrap();
+    assert_eq!(status.get(PlayerId(0)).score, BRAKE_SCORE_COST);
+    assert_eq!(status.breakdown(PlayerId(0)), vec![]);
+}